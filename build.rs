@@ -0,0 +1,49 @@
+//! Sets `CAZAN_COMMON_VERSION` to the version of `cazan-common` resolved in `Cargo.lock`, it is part of the keys of the
+//! prebuild cache so that the hitboxes are computed again when `cazan-common` is updated
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+fn main() {
+    let manifest_directory = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+    // The lock is at the root of the workspace, which may be above the package
+    let lock = manifest_directory
+        .ancestors()
+        .map(|directory| directory.join("Cargo.lock"))
+        .find(|lock| lock.is_file())
+        .expect("Cargo.lock is missing, it is written by cargo before the build");
+
+    println!("cargo:rerun-if-changed={}", lock.display());
+    println!("cargo:rerun-if-changed=build.rs");
+
+    let lock: toml::Table =
+        toml::from_str(&fs::read_to_string(&lock).unwrap()).expect("Cargo.lock is invalid");
+    let packages = lock["package"]
+        .as_array()
+        .expect("Cargo.lock has no packages");
+    let name = |package: &toml::Value| package["name"].as_str().unwrap_or_default().to_string();
+
+    // The dependency names the version only when several versions of `cazan-common` are locked
+    let dependency = packages
+        .iter()
+        .find(|package| name(package) == env::var("CARGO_PKG_NAME").unwrap())
+        .and_then(|package| package.get("dependencies")?.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(toml::Value::as_str)
+        .find(|dependency| dependency.split(' ').next() == Some("cazan-common"))
+        .expect("cazan-common isn't locked");
+
+    let version = match dependency.split(' ').nth(1) {
+        Some(version) => version.to_string(),
+        None => packages
+            .iter()
+            .find(|package| name(package) == "cazan-common")
+            .and_then(|package| package["version"].as_str())
+            .expect("cazan-common isn't locked")
+            .to_string(),
+    };
+
+    println!("cargo:rustc-env=CAZAN_COMMON_VERSION={version}");
+}
@@ -0,0 +1,144 @@
+//! Persistent cache of the prebuild results
+//! Entries are keyed by the asset checksum, the hitbox parameters and the version of `cazan-common`,
+//! so an unchanged sprite is never parsed twice
+
+use crate::hitbox::{Hitbox, HitboxSettings};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+/// Version of `cazan-common` used to compute the hitboxes, read from `Cargo.lock` by `build.rs`
+const CAZAN_COMMON_VERSION: &str = env!("CAZAN_COMMON_VERSION");
+
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct PreBuildCache {
+    entries: HashMap<String, Hitbox>,
+    #[serde(skip)]
+    used: HashSet<String>,
+}
+
+impl PreBuildCache {
+    /// Load the cache, a missing or unreadable cache file gives an empty cache
+    pub fn load(path: &Path) -> Self {
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|cache| serde_json::from_str(&cache).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), std::io::Error> {
+        let writer = fs::File::create(path)?;
+        serde_json::to_writer(writer, self)?;
+        Ok(())
+    }
+
+    /// The checksum of the sidecar of a sprite sheet is part of the key, the frames change with it
+    pub fn key(
+        checksum: &str,
+        settings: &HitboxSettings,
+        sidecar_checksum: Option<&str>,
+    ) -> String {
+        format!(
+            "{checksum}:{}:{}:{CAZAN_COMMON_VERSION}",
+            sidecar_checksum.unwrap_or_default(),
+            serde_json::to_string(settings).unwrap()
+        )
+    }
+
+    pub fn get(&mut self, key: &str) -> Option<&Hitbox> {
+        let hitbox = self.entries.get(key)?;
+        self.used.insert(key.to_string());
+        Some(hitbox)
+    }
+
+    pub fn insert(&mut self, key: String, hitbox: Hitbox) {
+        self.used.insert(key.clone());
+        self.entries.insert(key, hitbox);
+    }
+
+    /// Forget which entries were used, the next `prune` only keeps the entries used from now on
+    pub fn forget_usage(&mut self) {
+        self.used.clear();
+    }
+
+    /// Drop the entries that weren't used since the cache was loaded, or since `forget_usage`
+    pub fn prune(&mut self) {
+        self.entries.retain(|key, _| self.used.contains(key));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(epsilon: f64) -> HitboxSettings {
+        HitboxSettings {
+            epsilon,
+            alpha_threshold: 0,
+            erode: 0,
+            dilate: 0,
+            slicing: None,
+        }
+    }
+
+    #[test]
+    fn keys_change_with_the_inputs_of_the_hitbox() {
+        let key = PreBuildCache::key("abc", &settings(3.0), None);
+        assert_eq!(key, PreBuildCache::key("abc", &settings(3.0), None));
+        assert!(key.ends_with(&format!(":{CAZAN_COMMON_VERSION}")));
+
+        for other in [
+            PreBuildCache::key("abd", &settings(3.0), None),
+            PreBuildCache::key("abc", &settings(2.0), None),
+            PreBuildCache::key("abc", &settings(3.0), Some("def")),
+        ] {
+            assert_ne!(key, other);
+        }
+    }
+
+    #[test]
+    fn the_unused_entries_are_pruned() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("cache.json");
+
+        let mut cache = PreBuildCache::default();
+        cache.insert("kept".to_string(), Hitbox::Shapes(vec![]));
+        cache.insert("dropped".to_string(), Hitbox::Shapes(vec![]));
+        cache.save(&path).unwrap();
+
+        let mut cache = PreBuildCache::load(&path);
+        assert!(cache.get("kept").is_some());
+        assert!(cache.get("missing").is_none());
+        cache.prune();
+        cache.save(&path).unwrap();
+
+        let mut cache = PreBuildCache::load(&path);
+        assert!(cache.get("kept").is_some());
+        assert!(cache.get("dropped").is_none());
+    }
+
+    #[test]
+    fn the_usage_can_be_forgotten() {
+        let mut cache = PreBuildCache::default();
+        cache.insert("old".to_string(), Hitbox::Shapes(vec![]));
+        cache.insert("kept".to_string(), Hitbox::Shapes(vec![]));
+
+        cache.forget_usage();
+        assert!(cache.get("kept").is_some());
+        cache.prune();
+
+        assert!(cache.get("kept").is_some());
+        assert!(cache.get("old").is_none());
+    }
+
+    #[test]
+    fn an_unreadable_cache_is_empty() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("cache.json");
+        assert!(PreBuildCache::load(&path).entries.is_empty());
+
+        fs::write(&path, "{").unwrap();
+        assert!(PreBuildCache::load(&path).entries.is_empty());
+    }
+}
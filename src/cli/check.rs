@@ -0,0 +1,227 @@
+//! The `check` subcommand
+//! This command checks that the project is locked and prebuilt, without writing anything, so it can be used in CI
+//! Each class of problem has its own exit code, when several are found the code of the first one is used:
+//! - 2: the project file (`cazan.json`, `cazan.toml` or `cazan.yaml`) is invalid, too new or has unknown fields
+//! - 3: `.cazan/checksum.txt` doesn't match the project file
+//! - 4: `.cazan/config.json` doesn't match what `cazan lock` would write
+//! - 5: an asset glob doesn't match any file
+//! - 6: `.cazan/build/assets.json` has no entry for an asset
+
+use std::env;
+use std::fs;
+use std::process::ExitCode;
+
+use super::prebuild::{asset_files, asset_rules, SettingsOverrides};
+use super::{GlobalOptions, SubCommandTrait};
+use crate::config::{checksum, Config, ConfigOverlay};
+use crate::diagnostic::{Diagnostic, Level};
+use crate::hitbox;
+use crate::migration;
+use crate::project::{ProjectFile, ReadError};
+
+use crate::report::reporter;
+use argh::FromArgs;
+use glob::glob;
+use serde_json::Value;
+
+const EXIT_INVALID_CONFIG: u8 = 2;
+const EXIT_STALE_CHECKSUM: u8 = 3;
+const EXIT_STALE_LOCK: u8 = 4;
+const EXIT_UNMATCHED_GLOB: u8 = 5;
+const EXIT_MISSING_HITBOX: u8 = 6;
+
+/// A problem found by the check, the ones of `cazan.json` are shown in the file
+enum Problem {
+    Message(String),
+    Diagnostic(Diagnostic),
+}
+
+impl Problem {
+    fn report(&self) {
+        match self {
+            Problem::Message(message) => reporter().error(message),
+            Problem::Diagnostic(diagnostic) => reporter().diagnostic(diagnostic),
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, FromArgs)]
+#[argh(
+    subcommand,
+    name = "check",
+    description = "check that the project is locked and prebuilt, without writing anything"
+)]
+pub struct Check {}
+
+impl SubCommandTrait for Check {
+    fn run(&self, options: &GlobalOptions) -> ExitCode {
+        let current_dir = env::current_dir().unwrap();
+        let cazan_directory = current_dir.join(".cazan");
+
+        let project_file = match ProjectFile::find(&current_dir) {
+            Ok(Some(project_file)) if cazan_directory.exists() => project_file,
+            Ok(_) => {
+                reporter().error("cazan is not initialized for this directory");
+                return ExitCode::FAILURE;
+            }
+            Err(e) => {
+                reporter().error(&e);
+                return ExitCode::FAILURE;
+            }
+        };
+        let file_name = project_file.name();
+
+        let mut problems: Vec<(u8, Problem)> = vec![];
+
+        let source = match project_file.read().map(migration::upgrade) {
+            Ok(Ok((source, _))) => Some(source),
+            Ok(Err(e)) => {
+                problems.push((EXIT_INVALID_CONFIG, Problem::Message(e)));
+                None
+            }
+            Err(ReadError::Io(e)) => {
+                let message = format!("reading {file_name} file: {e}");
+                reporter().error(&message);
+                return ExitCode::FAILURE;
+            }
+            Err(ReadError::Syntax(diagnostic)) => {
+                problems.push((EXIT_INVALID_CONFIG, Problem::Diagnostic(*diagnostic)));
+                None
+            }
+        };
+
+        let config = match source
+            .as_ref()
+            .map(|source| (source, Config::parse(&source.json)))
+        {
+            Some((source, Ok((config, unknown)))) => {
+                for field in unknown {
+                    let diagnostic = Diagnostic::unknown_field(Level::Error, source, &field);
+                    problems.push((EXIT_INVALID_CONFIG, Problem::Diagnostic(diagnostic)));
+                }
+                for error in config.validate() {
+                    let diagnostic = Diagnostic::invalid_value(source, &error);
+                    problems.push((EXIT_INVALID_CONFIG, Problem::Diagnostic(diagnostic)));
+                }
+                Some(config)
+            }
+            Some((source, Err(e))) => {
+                let diagnostic = Diagnostic::from_json_error(source, &e);
+                problems.push((EXIT_INVALID_CONFIG, Problem::Diagnostic(diagnostic)));
+                None
+            }
+            None => None,
+        };
+
+        let overlay = match ConfigOverlay::new(options.profile.clone()) {
+            Ok(overlay) => Some(overlay),
+            Err(e) => {
+                problems.push((EXIT_INVALID_CONFIG, Problem::Message(e)));
+                None
+            }
+        };
+
+        // The rest of the checks use the config the way `cazan lock` resolves it
+        let resolved = match (&config, &source, &overlay) {
+            (Some(_), Some(source), Some(overlay)) => match overlay.apply(&source.json) {
+                Ok(resolved) => Some(resolved),
+                Err(e) => {
+                    problems.push((EXIT_INVALID_CONFIG, Problem::Message(e)));
+                    None
+                }
+            },
+            _ => None,
+        };
+        let config = resolved
+            .as_deref()
+            .and_then(|resolved| Config::parse(resolved).ok())
+            .map(|(config, _)| config);
+
+        let lock_command = options.lock_command();
+        let locked_checksum =
+            fs::read_to_string(cazan_directory.join("checksum.txt")).unwrap_or_default();
+        let lock_checksum = overlay.as_ref().and_then(|overlay| {
+            checksum(&project_file.path)
+                .ok()
+                .map(|checksum| overlay.lock_checksum(checksum))
+        });
+        if overlay.is_some() && lock_checksum != Some(locked_checksum) {
+            problems.push((
+                EXIT_STALE_CHECKSUM,
+                Problem::Message(format!(
+                    "`.cazan/checksum.txt` doesn't match {file_name}, use `{lock_command}` to update it"
+                )),
+            ));
+        }
+
+        if let Some(config) = &config {
+            let locked =
+                fs::read_to_string(cazan_directory.join("config.json")).unwrap_or_default();
+            // Compared as values, so that the formatting of the file doesn't matter
+            let locked = Config::parse(&locked)
+                .ok()
+                .and_then(|(locked, _)| serde_json::to_value(locked).ok());
+            if locked != serde_json::to_value(config).ok() {
+                problems.push((
+                    EXIT_STALE_LOCK,
+                    Problem::Message(format!(
+                        "`.cazan/config.json` doesn't match {file_name}, use `{lock_command}` to update it"
+                    )),
+                ));
+            }
+
+            for asset in config.assets.iter().flatten() {
+                let matches = glob(asset.pattern()).is_ok_and(|mut files| files.next().is_some());
+                if !matches {
+                    problems.push((
+                        EXIT_UNMATCHED_GLOB,
+                        Problem::Message(format!(
+                            "the asset glob `{}` doesn't match any file",
+                            asset.pattern()
+                        )),
+                    ));
+                }
+            }
+
+            let assets = config.assets.clone().unwrap_or_default();
+            if let Ok(rules) = asset_rules(&assets, config, SettingsOverrides::default()) {
+                let built: Option<Value> =
+                    fs::read_to_string(cazan_directory.join("build/assets.json"))
+                        .ok()
+                        .and_then(|built| serde_json::from_str(&built).ok());
+
+                for (file, _) in asset_files(&rules) {
+                    // The images without an alpha channel are skipped by the prebuild
+                    if !hitbox::has_alpha_channel(&file).unwrap_or(true) {
+                        continue;
+                    }
+                    let is_built = checksum(&file).is_ok_and(|checksum| {
+                        built
+                            .as_ref()
+                            .is_some_and(|built| built.get(&checksum).is_some())
+                    });
+                    if !is_built {
+                        problems.push((
+                            EXIT_MISSING_HITBOX,
+                            Problem::Message(format!(
+                                "`{}` has no hitbox in `.cazan/build/assets.json`, use `cazan prebuild` to build it",
+                                file.to_str().unwrap()
+                            )),
+                        ));
+                    }
+                }
+            }
+        }
+
+        if problems.is_empty() {
+            reporter().status("Checked", "the project is locked and prebuilt");
+            return ExitCode::SUCCESS;
+        }
+
+        for (_, problem) in &problems {
+            problem.report();
+        }
+
+        ExitCode::from(problems.iter().map(|(code, _)| *code).min().unwrap())
+    }
+}
@@ -0,0 +1,766 @@
+//! The `config` subcommand
+//! This command reads and edits the project file, `cazan.json`, `cazan.toml` or `cazan.yaml`
+//! The edits are checked with the `Config` type, but the file is written from its JSON value, so that the key order
+//! and the unknown fields are kept
+//! - `cazan.toml` is edited in place, its comments and its formatting are kept
+//! - `cazan.json` is written again with its indentation and its final line break, the rest of its spacing isn't kept
+//! - `cazan.yaml` is written again, it isn't edited when it has comments since they would be lost
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use super::lock::Lock;
+use super::{GlobalOptions, SubCommandTrait};
+use crate::config::Config;
+use crate::diagnostic::{Diagnostic, Level};
+use crate::migration;
+use crate::project::{self, ConfigSource, Format, ProjectFile};
+use crate::prompt;
+
+use crate::report::reporter;
+use argh::FromArgs;
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+#[derive(PartialEq, Debug, FromArgs)]
+#[argh(
+    subcommand,
+    name = "config",
+    description = "read and edit the project file (cazan.json, cazan.toml or cazan.yaml)"
+)]
+pub struct ConfigCommand {
+    #[argh(subcommand)]
+    pub subcommand: ConfigSubCommand,
+}
+
+#[derive(PartialEq, Debug, FromArgs)]
+#[argh(subcommand)]
+pub enum ConfigSubCommand {
+    Get(Get),
+    Set(Set),
+    AddAsset(AddAsset),
+    RemoveAsset(RemoveAsset),
+    AddAuthor(AddAuthor),
+    List(List),
+    Convert(Convert),
+}
+
+impl SubCommandTrait for ConfigCommand {
+    fn run(&self, options: &GlobalOptions) -> ExitCode {
+        match &self.subcommand {
+            ConfigSubCommand::Get(get) => get.run(options),
+            ConfigSubCommand::Set(set) => set.run(options),
+            ConfigSubCommand::AddAsset(add_asset) => add_asset.run(options),
+            ConfigSubCommand::RemoveAsset(remove_asset) => remove_asset.run(options),
+            ConfigSubCommand::AddAuthor(add_author) => add_author.run(options),
+            ConfigSubCommand::List(list) => list.run(options),
+            ConfigSubCommand::Convert(convert) => convert.run(options),
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, FromArgs)]
+#[argh(subcommand, name = "get", description = "print a value of the config")]
+pub struct Get {
+    #[argh(
+        positional,
+        description = "key of the value, the indexes of the lists are numbers (ex: assets.0.epsilon)"
+    )]
+    pub key: String,
+}
+
+impl SubCommandTrait for Get {
+    fn run(&self, _options: &GlobalOptions) -> ExitCode {
+        let Some(file) = EditedFile::open() else {
+            return ExitCode::FAILURE;
+        };
+
+        let Some(value) = get(&file.value, &key_path(&self.key)) else {
+            let message = format!("`{}` is not set in {}", self.key, file.name());
+            reporter().error(&message);
+            return ExitCode::FAILURE;
+        };
+
+        reporter().config_value(&self.key, value);
+        ExitCode::SUCCESS
+    }
+}
+
+#[derive(PartialEq, Debug, FromArgs)]
+#[argh(subcommand, name = "set", description = "set a value of the config")]
+pub struct Set {
+    #[argh(
+        positional,
+        description = "key of the value, the missing objects are created (ex: profiles.dev.rdpEpsilon)"
+    )]
+    pub key: String,
+
+    #[argh(
+        positional,
+        description = "the value, read as JSON if possible and as a string otherwise"
+    )]
+    pub value: String,
+
+    #[argh(switch, description = "lock the config afterwards, without asking")]
+    pub lock: bool,
+}
+
+impl SubCommandTrait for Set {
+    fn run(&self, options: &GlobalOptions) -> ExitCode {
+        let Some(file) = EditedFile::open() else {
+            return ExitCode::FAILURE;
+        };
+
+        let path = key_path(&self.key);
+        let value = serde_json::from_str(&self.value).unwrap_or(Value::String(self.value.clone()));
+
+        let mut edited = file.value.clone();
+        if let Err(e) = set(&mut edited, &path, value.clone()) {
+            reporter().error(&e);
+            return ExitCode::FAILURE;
+        }
+
+        // A value that looks like JSON but has the wrong type is retried as a string (ex: a name made of digits)
+        if !value.is_string() && !file.new_problems(&edited).is_empty() {
+            let mut as_string = file.value.clone();
+            set(&mut as_string, &path, Value::String(self.value.clone())).unwrap();
+            if file.new_problems(&as_string).is_empty() {
+                edited = as_string;
+            }
+        }
+
+        let message = format!("`{}` in {}", self.key, file.name());
+        file.save(edited, options, self.lock, "Set", &message)
+    }
+}
+
+#[derive(PartialEq, Debug, FromArgs)]
+#[argh(
+    subcommand,
+    name = "add-asset",
+    description = "add a glob pattern to the assets"
+)]
+pub struct AddAsset {
+    #[argh(
+        positional,
+        description = "glob pattern of the assets (ex: assets/sprite-*.png)"
+    )]
+    pub glob: String,
+
+    #[argh(switch, description = "lock the config afterwards, without asking")]
+    pub lock: bool,
+}
+
+impl SubCommandTrait for AddAsset {
+    fn run(&self, options: &GlobalOptions) -> ExitCode {
+        let Some(file) = EditedFile::open() else {
+            return ExitCode::FAILURE;
+        };
+
+        let mut edited = file.value.clone();
+        let assets = match list_mut(&mut edited, "assets") {
+            Ok(assets) => assets,
+            Err(e) => {
+                reporter().error(&e);
+                return ExitCode::FAILURE;
+            }
+        };
+        if assets
+            .iter()
+            .any(|asset| asset_pattern(asset) == Some(&self.glob))
+        {
+            let message = format!("`{}` is already in the assets", self.glob);
+            reporter().error(&message);
+            return ExitCode::FAILURE;
+        }
+        assets.push(Value::String(self.glob.clone()));
+
+        let message = format!("`{}` to the assets of {}", self.glob, file.name());
+        file.save(edited, options, self.lock, "Added", &message)
+    }
+}
+
+#[derive(PartialEq, Debug, FromArgs)]
+#[argh(
+    subcommand,
+    name = "remove-asset",
+    description = "remove the entries of a glob pattern from the assets"
+)]
+pub struct RemoveAsset {
+    #[argh(
+        positional,
+        description = "glob pattern of the entries, as written in the config"
+    )]
+    pub glob: String,
+
+    #[argh(switch, description = "lock the config afterwards, without asking")]
+    pub lock: bool,
+}
+
+impl SubCommandTrait for RemoveAsset {
+    fn run(&self, options: &GlobalOptions) -> ExitCode {
+        let Some(file) = EditedFile::open() else {
+            return ExitCode::FAILURE;
+        };
+
+        let mut edited = file.value.clone();
+        let assets = match list_mut(&mut edited, "assets") {
+            Ok(assets) => assets,
+            Err(e) => {
+                reporter().error(&e);
+                return ExitCode::FAILURE;
+            }
+        };
+        let count = assets.len();
+        assets.retain(|asset| asset_pattern(asset) != Some(&self.glob));
+        if assets.len() == count {
+            let message = format!("`{}` is not in the assets", self.glob);
+            reporter().error(&message);
+            return ExitCode::FAILURE;
+        }
+
+        let message = format!("`{}` from the assets of {}", self.glob, file.name());
+        file.save(edited, options, self.lock, "Removed", &message)
+    }
+}
+
+#[derive(PartialEq, Debug, FromArgs)]
+#[argh(
+    subcommand,
+    name = "add-author",
+    description = "add an author to the project"
+)]
+pub struct AddAuthor {
+    #[argh(positional, description = "name of the author")]
+    pub name: String,
+
+    #[argh(switch, description = "lock the config afterwards, without asking")]
+    pub lock: bool,
+}
+
+impl SubCommandTrait for AddAuthor {
+    fn run(&self, options: &GlobalOptions) -> ExitCode {
+        let Some(file) = EditedFile::open() else {
+            return ExitCode::FAILURE;
+        };
+
+        let mut edited = file.value.clone();
+        let authors = match list_mut(&mut edited, "authors") {
+            Ok(authors) => authors,
+            Err(e) => {
+                reporter().error(&e);
+                return ExitCode::FAILURE;
+            }
+        };
+        if authors
+            .iter()
+            .any(|author| author.as_str() == Some(&self.name))
+        {
+            let message = format!("`{}` is already an author", self.name);
+            reporter().error(&message);
+            return ExitCode::FAILURE;
+        }
+        authors.push(Value::String(self.name.clone()));
+
+        let message = format!("`{}` to the authors of {}", self.name, file.name());
+        file.save(edited, options, self.lock, "Added", &message)
+    }
+}
+
+#[derive(PartialEq, Debug, FromArgs)]
+#[argh(
+    subcommand,
+    name = "list",
+    description = "print all the values of the config, one key per line"
+)]
+pub struct List {}
+
+impl SubCommandTrait for List {
+    fn run(&self, _options: &GlobalOptions) -> ExitCode {
+        let Some(file) = EditedFile::open() else {
+            return ExitCode::FAILURE;
+        };
+
+        let mut values = vec![];
+        flatten(&file.value, String::new(), &mut values);
+        reporter().config_values(&values);
+        ExitCode::SUCCESS
+    }
+}
+
+#[derive(PartialEq, Debug, FromArgs)]
+#[argh(
+    subcommand,
+    name = "convert",
+    description = "convert the project file to another format, the previous file is removed"
+)]
+pub struct Convert {
+    #[argh(
+        option,
+        description = "format of the new project file: json, toml or yaml"
+    )]
+    pub to: Format,
+}
+
+impl SubCommandTrait for Convert {
+    fn run(&self, options: &GlobalOptions) -> ExitCode {
+        let Some(file) = EditedFile::open() else {
+            return ExitCode::FAILURE;
+        };
+        let file_name = file.name();
+
+        if file.project_file.format == self.to {
+            let message = format!("{file_name} is already in {}", self.to);
+            reporter().error(&message);
+            return ExitCode::FAILURE;
+        }
+
+        // The file is converted as is, so that the unknown fields and the invalid values are kept
+        let converted = match project::to_format(&file.value, self.to) {
+            Ok(converted) => converted,
+            Err(e) => {
+                let message = format!("converting {file_name} to {}: {e}", self.to);
+                reporter().error(&message);
+                return ExitCode::FAILURE;
+            }
+        };
+
+        let new_file_name = self.to.file_name();
+        if fs::write(file.directory.join(new_file_name), converted).is_err() {
+            let message = format!("creating {new_file_name}");
+            reporter().error(&message);
+            return ExitCode::FAILURE;
+        }
+
+        if fs::remove_file(&file.project_file.path).is_err() {
+            let message = format!("removing {file_name}, only one project file must be kept");
+            reporter().error(&message);
+            return ExitCode::FAILURE;
+        }
+
+        let message = format!(
+            "{file_name} to {new_file_name}, use `{}` to lock it",
+            options.lock_command()
+        );
+        reporter().status("Converted", &message);
+        ExitCode::SUCCESS
+    }
+}
+
+/// The project file being read or edited, as a JSON value
+pub(super) struct EditedFile {
+    directory: PathBuf,
+    project_file: ProjectFile,
+    /// The text of the file, to keep its formatting
+    pub text: String,
+    pub value: Value,
+}
+
+impl EditedFile {
+    /// Read the project file of the current directory, the problems are printed
+    pub fn open() -> Option<Self> {
+        let directory = env::current_dir().unwrap();
+
+        let project_file = match ProjectFile::find(&directory) {
+            Ok(Some(project_file)) => project_file,
+            Ok(None) => {
+                reporter().error("no project file found, use `cazan init` to create one");
+                return None;
+            }
+            Err(e) => {
+                reporter().error(&e);
+                return None;
+            }
+        };
+        let file_name = project_file.name();
+
+        let text = match fs::read_to_string(&project_file.path) {
+            Ok(text) => text,
+            Err(e) => {
+                let message = format!("reading {file_name} file: {e}");
+                reporter().error(&message);
+                return None;
+            }
+        };
+
+        let source = match ConfigSource::convert(file_name, project_file.format, text.clone()) {
+            Ok(source) => source,
+            Err(diagnostic) => {
+                reporter().diagnostic(&diagnostic);
+                return None;
+            }
+        };
+
+        let value = match serde_json::from_str(&source.json) {
+            Ok(value) => value,
+            Err(e) => {
+                reporter().diagnostic(&Diagnostic::from_json_error(&source, &e));
+                return None;
+            }
+        };
+
+        Some(Self {
+            directory,
+            project_file,
+            text,
+            value,
+        })
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.project_file.name()
+    }
+
+    /// The problems of `edited` that the file doesn't already have, so that an edit isn't refused because of another
+    /// value, and the ones fixing a value are accepted
+    fn new_problems(&self, edited: &Value) -> Vec<Diagnostic> {
+        let previous: Vec<String> = problems(self.name(), &self.value)
+            .into_iter()
+            .map(|(key, _)| key)
+            .collect();
+
+        problems(self.name(), edited)
+            .into_iter()
+            .filter(|(key, _)| !previous.contains(key))
+            .map(|(_, diagnostic)| diagnostic)
+            .collect()
+    }
+
+    /// The new text of the file for the edited value
+    pub fn to_text(&self, edited: &Value) -> Result<String, String> {
+        match self.project_file.format {
+            Format::Json => Ok(to_json(edited, &self.text)),
+            Format::Toml => project::edit_toml(&self.text, &self.value, edited),
+            Format::Yaml if project::has_yaml_comments(&self.text) => {
+                Err("its comments would be lost, edit it by hand".to_string())
+            }
+            Format::Yaml => project::to_format(edited, Format::Yaml),
+        }
+    }
+
+    /// Write the edited value if it has no new problem, then lock it if asked to
+    pub fn save(
+        self,
+        edited: Value,
+        options: &GlobalOptions,
+        lock: bool,
+        label: &str,
+        message: &str,
+    ) -> ExitCode {
+        // The fields of a newer config may not mean the same for this version of cazan
+        if let Err(e) = migration::understood_version(&self.value, self.name()) {
+            reporter().error(&e);
+            return ExitCode::FAILURE;
+        }
+
+        let problems = self.new_problems(&edited);
+        if !problems.is_empty() {
+            for problem in problems {
+                reporter().diagnostic(&problem);
+            }
+            return ExitCode::FAILURE;
+        }
+
+        let file_name = self.name();
+        let text = match self.to_text(&edited) {
+            Ok(text) => text,
+            Err(e) => {
+                let message = format!("writing {file_name}: {e}");
+                reporter().error(&message);
+                return ExitCode::FAILURE;
+            }
+        };
+
+        if fs::write(&self.project_file.path, text).is_err() {
+            let message = format!("writing {file_name}");
+            reporter().error(&message);
+            return ExitCode::FAILURE;
+        }
+        reporter().status(label, message);
+
+        // The locked config can only be updated once cazan is initialized
+        if !self.directory.join(".cazan").exists() {
+            return ExitCode::SUCCESS;
+        }
+
+        let lock = lock
+            || prompt::is_interactive()
+                && prompt::ask_parsed("Lock the new config? (y/n)", "y", |answer| {
+                    match answer.to_lowercase().as_str() {
+                        "y" | "yes" => Ok(true),
+                        "n" | "no" => Ok(false),
+                        _ => Err("Answer with y or n".to_string()),
+                    }
+                })
+                .is_some_and(|answer| answer.unwrap_or(true));
+
+        if !lock {
+            let message = format!(
+                "the locked config is not up-to-date, use `{}` to update it",
+                options.lock_command()
+            );
+            reporter().warning(&message);
+            return ExitCode::SUCCESS;
+        }
+
+        Lock {
+            force: false,
+            allow_unknown: false,
+        }
+        .run(options)
+    }
+}
+
+/// The problems of a config, with a key to compare them (the unknown field, the invalid value or the error)
+fn problems(file_name: &str, value: &Value) -> Vec<(String, Diagnostic)> {
+    let source = ConfigSource::unsaved(file_name, serde_json::to_string_pretty(value).unwrap());
+
+    match Config::parse(&source.json) {
+        Ok((config, unknown)) => unknown
+            .into_iter()
+            .map(|field| {
+                let diagnostic = Diagnostic::unknown_field(Level::Error, &source, &field);
+                (field, diagnostic)
+            })
+            .chain(config.validate().into_iter().map(|error| {
+                let diagnostic = Diagnostic::invalid_value(&source, &error);
+                (error.to_string(), diagnostic)
+            }))
+            .collect(),
+        Err(e) => {
+            let diagnostic = Diagnostic::from_json_error(&source, &e);
+            // The position moves when lines are added, the problem is the same
+            let message = e.to_string();
+            let key = message
+                .rsplit_once(" at line ")
+                .map_or(message.as_str(), |(key, _)| key);
+            vec![(key.to_string(), diagnostic)]
+        }
+    }
+}
+
+/// Write the value as JSON with the indentation of the previous text of the file, and its final line break
+fn to_json(value: &Value, previous: &str) -> String {
+    let indent = previous
+        .lines()
+        .map(|line| &line[..line.len() - line.trim_start().len()])
+        .find(|indent| !indent.is_empty())
+        .unwrap_or("  ");
+
+    let mut json = vec![];
+    let formatter = serde_json::ser::PrettyFormatter::with_indent(indent.as_bytes());
+    let mut serializer = serde_json::Serializer::with_formatter(&mut json, formatter);
+    value.serialize(&mut serializer).unwrap();
+
+    let mut json = String::from_utf8(json).unwrap();
+    if previous.ends_with('\n') {
+        json.push('\n');
+    }
+    json
+}
+
+/// The segments of a key, `assets[0].epsilon` is the same as `assets.0.epsilon`
+fn key_path(key: &str) -> Vec<String> {
+    key.replace('[', ".")
+        .replace(']', "")
+        .split('.')
+        .filter(|segment| !segment.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn get<'a>(value: &'a Value, path: &[String]) -> Option<&'a Value> {
+    path.iter().try_fold(value, |value, segment| match value {
+        Value::Object(fields) => fields.get(segment),
+        Value::Array(items) => items.get(segment.parse::<usize>().ok()?),
+        _ => None,
+    })
+}
+
+/// Set the value at `path`, the missing objects on the way are created
+fn set(value: &mut Value, path: &[String], new_value: Value) -> Result<(), String> {
+    let Some((segment, rest)) = path.split_first() else {
+        *value = new_value;
+        return Ok(());
+    };
+
+    if value.is_null() {
+        *value = Value::Object(Map::new());
+    }
+
+    let child = match value {
+        Value::Object(fields) => fields.entry(segment.clone()).or_insert(Value::Null),
+        Value::Array(items) => {
+            let length = items.len();
+            segment
+                .parse::<usize>()
+                .ok()
+                .and_then(|index| items.get_mut(index))
+                .ok_or_else(|| format!("`{segment}` is not an index of a list of {length} items"))?
+        }
+        value => {
+            return Err(format!(
+                "`{segment}` can't be set in a {}",
+                type_name(value)
+            ))
+        }
+    };
+
+    set(child, rest, new_value)
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "list",
+        Value::Object(_) => "object",
+    }
+}
+
+/// A top-level list of the config, it is created if missing
+fn list_mut<'a>(value: &'a mut Value, field: &str) -> Result<&'a mut Vec<Value>, String> {
+    let Some(fields) = value.as_object_mut() else {
+        return Err("the config must be an object".to_string());
+    };
+    let list = fields.entry(field).or_insert(Value::Null);
+    if !list.is_array() {
+        *list = Value::Array(vec![]);
+    }
+    Ok(list.as_array_mut().unwrap())
+}
+
+/// The glob pattern of an entry of `assets`
+fn asset_pattern(asset: &Value) -> Option<&String> {
+    match asset {
+        Value::String(pattern) => Some(pattern),
+        Value::Object(asset) => match asset.get("pattern")? {
+            Value::String(pattern) => Some(pattern),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// The leaves of a value with their key, the empty lists and objects are leaves
+fn flatten<'a>(value: &'a Value, key: String, values: &mut Vec<(String, &'a Value)>) {
+    let child_key = |segment: &str| {
+        if key.is_empty() {
+            segment.to_string()
+        } else {
+            format!("{key}.{segment}")
+        }
+    };
+
+    match value {
+        Value::Object(fields) if !fields.is_empty() => {
+            for (field, value) in fields {
+                flatten(value, child_key(field), values);
+            }
+        }
+        Value::Array(items) if !items.is_empty() => {
+            for (i, value) in items.iter().enumerate() {
+                flatten(value, child_key(&i.to_string()), values);
+            }
+        }
+        value => values.push((key, value)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn key_paths() {
+        assert_eq!(key_path("assets[0].epsilon"), ["assets", "0", "epsilon"]);
+        assert_eq!(key_path("assets.0.epsilon"), ["assets", "0", "epsilon"]);
+        assert_eq!(key_path("name"), ["name"]);
+    }
+
+    #[test]
+    fn set_creates_the_missing_objects() {
+        let mut value = json!({ "name": "game" });
+        set(&mut value, &key_path("profiles.dev.rdpEpsilon"), json!(6)).unwrap();
+        assert_eq!(
+            value,
+            json!({ "name": "game", "profiles": { "dev": { "rdpEpsilon": 6 } } })
+        );
+    }
+
+    #[test]
+    fn set_in_lists() {
+        let mut value = json!({ "assets": [{ "pattern": "*.png" }] });
+        set(&mut value, &key_path("assets[0].epsilon"), json!(2)).unwrap();
+        assert_eq!(value["assets"][0]["epsilon"], 2);
+
+        let error = set(&mut value, &key_path("assets.3"), json!("x")).unwrap_err();
+        assert_eq!(error, "`3` is not an index of a list of 1 items");
+        let error = set(&mut value, &key_path("assets.0.pattern.x"), json!(1)).unwrap_err();
+        assert_eq!(error, "`x` can't be set in a string");
+    }
+
+    #[test]
+    fn get_values() {
+        let value = json!({ "assets": ["a", { "pattern": "b" }] });
+        assert_eq!(
+            get(&value, &key_path("assets.1.pattern")),
+            Some(&json!("b"))
+        );
+        assert_eq!(get(&value, &key_path("assets.2")), None);
+    }
+
+    #[test]
+    fn lists_of_a_config_that_isnt_an_object() {
+        let mut value = json!({ "name": "game" });
+        list_mut(&mut value, "assets").unwrap().push(json!("*.png"));
+        assert_eq!(value, json!({ "name": "game", "assets": ["*.png"] }));
+
+        let mut value = json!([]);
+        assert_eq!(
+            list_mut(&mut value, "assets"),
+            Err("the config must be an object".to_string())
+        );
+    }
+
+    #[test]
+    fn flatten_values() {
+        let value = json!({ "name": "game", "authors": [], "assets": ["a", { "pattern": "b" }] });
+        let mut values = vec![];
+        flatten(&value, String::new(), &mut values);
+        assert_eq!(
+            values,
+            [
+                ("name".to_string(), &json!("game")),
+                ("authors".to_string(), &json!([])),
+                ("assets.0".to_string(), &json!("a")),
+                ("assets.1.pattern".to_string(), &json!("b")),
+            ]
+        );
+    }
+
+    #[test]
+    fn json_keeps_the_indentation() {
+        let value = json!({ "name": "game", "authors": ["a"] });
+        let text = to_json(&value, "{\n    \"name\": \"x\"\n}\n");
+        assert_eq!(
+            text,
+            "{\n    \"name\": \"game\",\n    \"authors\": [\n        \"a\"\n    ]\n}\n"
+        );
+    }
+
+    #[test]
+    fn asset_patterns() {
+        assert_eq!(asset_pattern(&json!("a")), Some(&"a".to_string()));
+        assert_eq!(
+            asset_pattern(&json!({ "pattern": "b" })),
+            Some(&"b".to_string())
+        );
+        assert_eq!(asset_pattern(&json!(1)), None);
+    }
+}
@@ -1,84 +1,591 @@
-use crate::cli::SubCommandTrait;
-use crate::config::Config;
+use super::prebuild::DEFAULT_EPSILON;
+use crate::cli::{GlobalOptions, SubCommandTrait};
+use crate::config::{self, AssetConfig, Config};
+use crate::diagnostic::Diagnostic;
+use crate::migration::CONFIG_VERSION;
+use crate::project::{self, ConfigSource, Format, ProjectFile};
+use crate::prompt;
+use crate::report::reporter;
+use crate::template::{Template, Variables};
 use argh::FromArgs;
-use cprint::{ceprintln, cprintln};
+use chrono::{Local, NaiveDateTime};
 use semver::Version;
+use std::path::{Path, PathBuf};
 use std::process::ExitCode;
 use std::{env, fs};
 
+/// `$schema` of the generated project file, the schema is written by init
+const SCHEMA_REFERENCE: &str = "./.cazan/schema.json";
+/// Directory of `.cazan` where `init --force` backs up the files it overwrites
+const BACKUPS_DIRECTORY: &str = "backups";
+/// Number of backups kept, the oldest ones are removed
+const MAX_BACKUPS: usize = 10;
+/// Name of a backup, the ones made in the same second get a suffix from 2 (`20261018-143000-2`)
+const BACKUP_TIMESTAMP: &str = "%Y%m%d-%H%M%S";
+
 #[derive(PartialEq, Debug, FromArgs)]
 #[argh(subcommand, name = "init", description = "Init your Cazan project")]
 pub struct Init {
     #[argh(
         switch,
         short = 'f',
-        description = "force re-initialization of your project"
+        description = "regenerate the project file, the overwritten files are backed up in .cazan/backups"
     )]
     pub force: bool,
+
+    #[argh(
+        switch,
+        description = "list what would be created or overwritten, without writing anything"
+    )]
+    pub dry_run: bool,
+
+    #[argh(
+        option,
+        short = 't',
+        description = "lay down a template: default, spritesheet, minimal or the path of a template directory"
+    )]
+    pub template: Option<String>,
+
+    #[argh(
+        switch,
+        short = 'y',
+        description = "use the default values instead of prompting for the missing ones"
+    )]
+    pub yes: bool,
+
+    #[argh(
+        option,
+        description = "name of the project (default: the directory name)"
+    )]
+    pub name: Option<String>,
+
+    #[argh(option, description = "version of the project (default: 0.0.1)")]
+    pub version: Option<Version>,
+
+    #[argh(option, description = "an author of the project, can be repeated")]
+    pub author: Vec<String>,
+
+    #[argh(
+        option,
+        description = "a glob pattern of the assets (ex: assets/sprite-*.png), can be repeated"
+    )]
+    pub asset: Vec<String>,
+
+    #[argh(
+        option,
+        description = "epsilon value for the Ramer-Douglas-Peucker algorithm (Image simplification)"
+    )]
+    pub rdp_epsilon: Option<f64>,
+
+    #[argh(option, description = "use autoplay for multimedia (true or false)")]
+    pub autoplay: Option<bool>,
 }
 
-impl SubCommandTrait for Init {
-    fn run(&self) -> ExitCode {
-        let current_dir = env::current_dir().unwrap();
-        let dir_name = &current_dir.file_name().unwrap().to_str().unwrap();
-        let config = Config {
-            name: dir_name,
-            version: Version::new(0, 0, 1),
-            authors: Vec::new(),
-            use_autoplay_for_multimedia: None,
-            rdp_epsilon: None,
-            assets: Some(vec![]),
-            plugins: Some(vec![]),
+/// The values of the new config, given with the flags or answered to the prompts
+struct Answers {
+    name: String,
+    version: Version,
+    authors: Vec<String>,
+    assets: Vec<String>,
+    rdp_epsilon: Option<f64>,
+    autoplay: Option<bool>,
+}
+
+impl Init {
+    /// Prompt for the values not given with the flags, the defaults are used when stdin is not a terminal
+    /// Returns `None` if a prompt was cancelled
+    fn answers(&self, dir_name: &str) -> Option<Answers> {
+        let mut answers = Answers {
+            name: self.name.clone().unwrap_or(dir_name.to_string()),
+            version: self.version.clone().unwrap_or(Version::new(0, 0, 1)),
+            authors: self.author.clone(),
+            assets: self.asset.clone(),
+            rdp_epsilon: self.rdp_epsilon,
+            autoplay: self.autoplay,
+        };
+
+        if self.yes || self.dry_run || !prompt::is_interactive() {
+            return Some(answers);
+        }
+
+        if self.name.is_none() {
+            let name = prompt::ask("Project name", &answers.name)?;
+            if !name.is_empty() {
+                answers.name = name;
+            }
+        }
+
+        if self.version.is_none() {
+            let version = prompt::ask_parsed("Version", &answers.version.to_string(), |answer| {
+                Version::parse(answer).map_err(|e| format!("Invalid version: {e}"))
+            })?;
+            if let Some(version) = version {
+                answers.version = version;
+            }
+        }
+
+        if self.author.is_empty() {
+            answers.authors = split_list(&prompt::ask("Authors, separated by commas", "")?);
+        }
+
+        if self.asset.is_empty() {
+            answers.assets = split_list(&prompt::ask(
+                "Asset glob patterns, separated by commas (ex: assets/*.png)",
+                "",
+            )?);
+        }
+
+        if self.rdp_epsilon.is_none() {
+            answers.rdp_epsilon = prompt::ask_parsed(
+                "Epsilon for the Ramer-Douglas-Peucker algorithm",
+                &DEFAULT_EPSILON.to_string(),
+                |answer| match answer.parse::<f64>() {
+                    Ok(epsilon) if epsilon.is_finite() && epsilon > 0. => Ok(epsilon),
+                    _ => Err("The epsilon must be a positive number".to_string()),
+                },
+            )?;
+        }
+
+        if self.autoplay.is_none() {
+            answers.autoplay = prompt::ask_parsed(
+                "Use autoplay for multimedia? (y/n)",
+                "",
+                |answer| match answer.to_lowercase().as_str() {
+                    "y" | "yes" => Ok(true),
+                    "n" | "no" => Ok(false),
+                    _ => Err("Answer with y or n".to_string()),
+                },
+            )?;
+        }
+
+        Some(answers)
+    }
+}
+
+impl Answers {
+    /// The new project file in `format`, based on the `cazan.json` of the template if any, or the problems of the values
+    /// The answers replace the name and version of the template, and are added to its authors and assets
+    fn to_config(
+        &self,
+        template_config: Option<&ConfigSource>,
+        format: Format,
+    ) -> Result<String, Vec<Diagnostic>> {
+        let mut config = match template_config {
+            Some(template_config) => match Config::parse(&template_config.json) {
+                Ok((config, _)) => config,
+                Err(e) => return Err(vec![Diagnostic::from_json_error(template_config, &e)]),
+            },
+            None => Config {
+                schema: None,
+                config_version: None,
+                name: String::new(),
+                version: Version::new(0, 0, 1),
+                authors: Vec::new(),
+                use_autoplay_for_multimedia: None,
+                rdp_epsilon: None,
+                alpha_threshold: None,
+                erode: None,
+                dilate: None,
+                assets: Some(vec![]),
+                plugins: Some(vec![]),
+                max_logs: None,
+                profiles: None,
+            },
         };
 
-        let serialized_config = serde_json::to_string_pretty(&config).unwrap();
+        config.schema.get_or_insert(SCHEMA_REFERENCE.to_string());
+        config.config_version = Some(CONFIG_VERSION);
+        config.name = self.name.clone();
+        config.version = self.version.clone();
+        config.authors.extend(self.authors.iter().cloned());
+        config
+            .assets
+            .get_or_insert_with(Vec::new)
+            .extend(self.assets.iter().cloned().map(AssetConfig::Pattern));
+        if self.rdp_epsilon.is_some() {
+            config.rdp_epsilon = self.rdp_epsilon;
+        }
+        if self.autoplay.is_some() {
+            config.use_autoplay_for_multimedia = self.autoplay;
+        }
+
+        // The problems are shown in the generated config, as JSON
+        let errors = config.validate();
+        if !errors.is_empty() {
+            let json = serde_json::to_string_pretty(&config).unwrap();
+            let source = ConfigSource::json(format.file_name(), json);
+            return Err(errors
+                .iter()
+                .map(|error| Diagnostic::invalid_value(&source, error))
+                .collect());
+        }
+
+        Ok(project::to_format(&config, format).unwrap())
+    }
+}
+
+fn split_list(answer: &str) -> Vec<String> {
+    answer
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(str::to_string)
+        .collect()
+}
 
-        // Create .cazan dir
+impl SubCommandTrait for Init {
+    fn run(&self, options: &GlobalOptions) -> ExitCode {
+        let current_dir = env::current_dir().unwrap();
+        let dir_name = &current_dir.file_name().unwrap().to_str().unwrap();
         let dot_cazan_dir = current_dir.join(".cazan");
+        let schema_file = dot_cazan_dir.join("schema.json");
 
-        if self.force
-            && dot_cazan_dir.exists()
-            && fs::remove_dir_all(dot_cazan_dir.clone()).is_err()
-        {
-            ceprintln!("Error recreating .cazan directory");
+        // The existing project file is regenerated in its format, a new one is in the format of `--manifest-path`,
+        // or in JSON
+        let existing_file = match ProjectFile::find(&current_dir) {
+            Ok(existing_file) => existing_file,
+            Err(e) => {
+                reporter().error(&e);
+                return ExitCode::FAILURE;
+            }
+        };
+        let format = existing_file
+            .as_ref()
+            .map(|file| file.format)
+            .or(options.manifest_format())
+            .unwrap_or(Format::Json);
+        let file_name = format.file_name();
+        let config_file = current_dir.join(file_name);
+
+        if config_file.exists() && dot_cazan_dir.exists() && !self.force {
+            let message = format!("cazan is already initialized for this directory (try using --force to regenerate {file_name})");
+            reporter().error(&message);
             return ExitCode::FAILURE;
         }
 
-        if fs::create_dir(dot_cazan_dir.clone()).is_err() {
-            let message = if dot_cazan_dir.exists() {
-                "Error creating .cazan directory: Already existing (try using --force)"
-            } else {
-                &*format!(
-                    "Error {}creating .cazan directory",
-                    if self.force && dot_cazan_dir.exists() {
-                        "-re"
+        let template = match self.template.as_deref().map(Template::load).transpose() {
+            Ok(template) => template,
+            Err(e) => {
+                reporter().error(&e);
+                return ExitCode::FAILURE;
+            }
+        };
+
+        // The prompts are only shown, and the template only laid down, when the project file is (re)generated
+        let mut template_files: Vec<(PathBuf, Vec<u8>)> = vec![];
+        let serialized_config = if config_file.exists() && !self.force {
+            if template.is_some() {
+                let message = format!("--template is ignored, {file_name} already exists (use --force to regenerate it)");
+                reporter().warning(&message);
+            }
+            None
+        } else {
+            let Some(answers) = self.answers(dir_name) else {
+                reporter().error("initialization cancelled");
+                return ExitCode::FAILURE;
+            };
+
+            let mut template_config = None;
+            if let Some(template) = &template {
+                let version = answers.version.to_string();
+                let variables = Variables {
+                    name: &answers.name,
+                    version: &version,
+                };
+                for (path, content) in template.render(&variables) {
+                    if path == Path::new("cazan.json") {
+                        template_config = Some(String::from_utf8_lossy(&content).into_owned());
                     } else {
-                        ""
+                        template_files.push((path, content));
+                    }
+                }
+            }
+
+            let template_file = format!("{}/cazan.json", self.template.as_deref().unwrap_or("."));
+            let template_config =
+                template_config.map(|config| ConfigSource::json(&template_file, config));
+            match answers.to_config(template_config.as_ref(), format) {
+                Ok(config) => Some(config),
+                Err(errors) => {
+                    for error in errors {
+                        reporter().diagnostic(&error);
                     }
-                )
+                    return ExitCode::FAILURE;
+                }
+            }
+        };
+
+        // The files overwritten by init are backed up before the project file is overwritten, the `.gitignore` files
+        // only get lines added
+        let backup = (self.force && config_file.exists()).then(|| backup_directory(&dot_cazan_dir));
+        let overwritten: Vec<PathBuf> = [&config_file, &schema_file]
+            .into_iter()
+            .filter(|file| file.exists())
+            .cloned()
+            .collect();
+        let overwritten_names = overwritten
+            .iter()
+            .map(|file| relative(&current_dir, file))
+            .collect::<Vec<_>>()
+            .join(" and ");
+
+        if self.dry_run {
+            if let Some(backup) = &backup {
+                let message = format!(
+                    "back up {overwritten_names} to `{}`",
+                    relative(&current_dir, backup)
+                );
+                reporter().status("Would", &message);
+            }
+            if !dot_cazan_dir.exists() {
+                reporter().status("Would", "create .cazan");
+            }
+            let action = if schema_file.exists() {
+                "overwrite"
+            } else {
+                "create"
             };
-            ceprintln!(message);
+            reporter().status("Would", &format!("{action} .cazan/schema.json"));
+            if serialized_config.is_some() {
+                let action = if config_file.exists() {
+                    "overwrite"
+                } else {
+                    "create"
+                };
+                reporter().status("Would", &format!("{action} {file_name}"));
+            }
+            for (path, _) in &template_files {
+                let action = if !current_dir.join(path).exists() {
+                    "create"
+                } else if path.file_name() == Some(".gitignore".as_ref()) {
+                    "add the missing lines to"
+                } else {
+                    "keep the existing"
+                };
+                reporter().status("Would", &format!("{action} `{}`", path.display()));
+            }
+            return ExitCode::SUCCESS;
+        }
+
+        if let Some(backup) = &backup {
+            if let Err(e) = back_up(&overwritten, &current_dir, backup) {
+                let message = format!("backing up the previous config: {e}");
+                reporter().error(&message);
+                return ExitCode::FAILURE;
+            }
+            let message = format!(
+                "{overwritten_names} to `{}`",
+                relative(&current_dir, backup)
+            );
+            reporter().status("Backed up", &message);
+        }
+
+        // Create .cazan dir, the files of the other subcommands (locked config, build) are kept
+        if !dot_cazan_dir.exists() && fs::create_dir(&dot_cazan_dir).is_err() {
+            reporter().error("creating .cazan directory");
             return ExitCode::FAILURE;
         }
 
-        // Create cazan.json file
-        let config_file = current_dir.join("cazan.json");
+        // The schema is regenerated, so that it matches the version of cazan
+        let schema = serde_json::to_string_pretty(&config::schema()).unwrap();
+        if fs::write(&schema_file, schema).is_err() {
+            reporter().error("creating .cazan/schema.json");
+            return ExitCode::FAILURE;
+        }
 
+        // Create the project file
         // If there's already a config file but the "--force" is not used
-        if config_file.exists() && !self.force {
+        let Some(serialized_config) = serialized_config else {
+            let message = format!("cazan project with the existing {file_name}");
+            reporter().status("Initialized", &message);
             return ExitCode::SUCCESS;
-        }
+        };
 
         if fs::write(config_file, serialized_config).is_err() {
             let message = format!(
-                "Error {}creating cazan.json with default config",
+                "{}creating {file_name} with default config",
                 if self.force { "re-" } else { "" }
             );
-            ceprintln!(message);
+            reporter().error(&message);
             return ExitCode::FAILURE;
         }
 
-        cprintln!("Initialized cazan project");
+        for (path, content) in template_files {
+            if let Err(e) = write_template_file(&current_dir, &path, &content) {
+                let message = format!("creating `{}`: {e}", path.display());
+                reporter().error(&message);
+                return ExitCode::FAILURE;
+            }
+        }
+
+        if self.force {
+            let message = format!("cazan project, use `cazan lock` to lock the new {file_name}");
+            reporter().status("Initialized", &message);
+        } else {
+            reporter().status("Initialized", "cazan project");
+        }
         ExitCode::SUCCESS
     }
 }
+
+/// A new timestamped directory in `.cazan/backups`
+fn backup_directory(dot_cazan_dir: &Path) -> PathBuf {
+    let timestamp = Local::now().format(BACKUP_TIMESTAMP).to_string();
+    let mut backup = dot_cazan_dir.join(BACKUPS_DIRECTORY).join(&timestamp);
+    let mut i = 1;
+    while backup.exists() {
+        i += 1;
+        backup = dot_cazan_dir
+            .join(BACKUPS_DIRECTORY)
+            .join(format!("{timestamp}-{i}"));
+    }
+    backup
+}
+
+/// Copy the files to `backup` at their path relative to `root`, then remove the oldest backups to keep `MAX_BACKUPS`
+fn back_up(files: &[PathBuf], root: &Path, backup: &Path) -> std::io::Result<()> {
+    for file in files {
+        let to = backup.join(file.strip_prefix(root).unwrap_or(file));
+        fs::create_dir_all(to.parent().unwrap())?;
+        fs::copy(file, to)?;
+    }
+
+    rotate_backups(backup.parent().unwrap(), MAX_BACKUPS)
+}
+
+/// Remove the oldest backups so that `keep` of them are left, the other directories are left as is
+fn rotate_backups(backups_directory: &Path, keep: usize) -> std::io::Result<()> {
+    let mut backups: Vec<((NaiveDateTime, u32), PathBuf)> = fs::read_dir(backups_directory)?
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|path| path.is_dir())
+        .filter_map(|path| Some((backup_order(path.file_name()?.to_str()?)?, path)))
+        .collect();
+    backups.sort();
+
+    let removed = backups.len().saturating_sub(keep);
+    for (_, backup) in &backups[..removed] {
+        fs::remove_dir_all(backup)?;
+    }
+    Ok(())
+}
+
+/// The time and the suffix of the name of a backup, in the order of the backups
+fn backup_order(name: &str) -> Option<(NaiveDateTime, u32)> {
+    let (time, suffix) = match name.rsplit_once('-') {
+        Some((time, suffix)) if time.contains('-') => (time, suffix.parse().ok()?),
+        _ => (name, 1),
+    };
+    Some((
+        NaiveDateTime::parse_from_str(time, BACKUP_TIMESTAMP).ok()?,
+        suffix,
+    ))
+}
+
+fn relative(root: &Path, path: &Path) -> String {
+    path.strip_prefix(root)
+        .unwrap_or(path)
+        .display()
+        .to_string()
+}
+
+/// Write a file of a template, the existing files are kept, except the `.gitignore` files which get the missing lines
+fn write_template_file(root: &Path, file: &Path, content: &[u8]) -> std::io::Result<()> {
+    let path = &root.join(file);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    if !path.exists() {
+        return fs::write(path, content);
+    }
+
+    if path.file_name() == Some(".gitignore".as_ref()) {
+        let mut gitignore = fs::read_to_string(path)?;
+        let missing: Vec<&str> = std::str::from_utf8(content)
+            .unwrap_or_default()
+            .lines()
+            .filter(|line| !gitignore.lines().any(|existing| existing == *line))
+            .collect();
+        if !missing.is_empty() {
+            if !gitignore.is_empty() && !gitignore.ends_with('\n') {
+                gitignore.push('\n');
+            }
+            gitignore.push_str(&(missing.join("\n") + "\n"));
+            fs::write(path, gitignore)?;
+        }
+        return Ok(());
+    }
+
+    let warning = format!("`{}` already exists, it is kept", file.display());
+    reporter().warning(&warning);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(directory: &Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(directory)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn only_the_overwritten_files_are_backed_up() {
+        let root = tempfile::tempdir().unwrap();
+        let root = root.path();
+        let dot_cazan_dir = root.join(".cazan");
+        fs::create_dir_all(dot_cazan_dir.join("build")).unwrap();
+        fs::write(root.join("cazan.json"), "{}").unwrap();
+        fs::write(dot_cazan_dir.join("schema.json"), "{}").unwrap();
+        fs::write(dot_cazan_dir.join("build/assets.json"), "{}").unwrap();
+
+        let backup = backup_directory(&dot_cazan_dir);
+        let files = [root.join("cazan.json"), dot_cazan_dir.join("schema.json")];
+        back_up(&files, root, &backup).unwrap();
+
+        assert_eq!(names(&backup), [".cazan", "cazan.json"]);
+        assert_eq!(names(&backup.join(".cazan")), ["schema.json"]);
+    }
+
+    #[test]
+    fn the_oldest_backups_are_removed() {
+        let backups = tempfile::tempdir().unwrap();
+        let backups = backups.path();
+        for name in [
+            "20260103-120000",
+            "20260101-120000",
+            "20260102-120000",
+            "20260102-120000-2",
+        ] {
+            fs::create_dir(backups.join(name)).unwrap();
+        }
+        fs::create_dir(backups.join("mine")).unwrap();
+        fs::write(backups.join("notes.txt"), "").unwrap();
+
+        rotate_backups(backups, 2).unwrap();
+        assert_eq!(
+            names(backups),
+            ["20260102-120000-2", "20260103-120000", "mine", "notes.txt"]
+        );
+    }
+
+    #[test]
+    fn the_suffixes_of_a_second_are_numbers() {
+        let backups = tempfile::tempdir().unwrap();
+        let backups = backups.path();
+        fs::create_dir(backups.join("20260101-120000")).unwrap();
+        for i in 2..=12 {
+            fs::create_dir(backups.join(format!("20260101-120000-{i}"))).unwrap();
+        }
+
+        rotate_backups(backups, MAX_BACKUPS).unwrap();
+        let mut expected: Vec<String> = (3..=12).map(|i| format!("20260101-120000-{i}")).collect();
+        expected.sort();
+        assert_eq!(names(backups), expected);
+    }
+}
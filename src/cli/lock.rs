@@ -1,7 +1,10 @@
-use crate::cli::SubCommandTrait;
-use crate::config::{checksum, Config};
+use crate::cli::{GlobalOptions, SubCommandTrait};
+use crate::config::{checksum, Config, ConfigOverlay};
+use crate::diagnostic::{Diagnostic, Level};
+use crate::migration::{self, CONFIG_VERSION};
+use crate::project::{ConfigSource, ProjectFile, ReadError};
+use crate::report::reporter;
 use argh::FromArgs;
-use cprint::{ceprintln, cprintln};
 use std::env;
 use std::fs;
 use std::process::ExitCode;
@@ -12,7 +15,7 @@ pub struct Lock {
     #[argh(
         switch,
         short = 'f',
-        description = "force locking the cazan.json file, without checking if the JSON is valid"
+        description = "force locking the project file (cazan.json, cazan.toml or cazan.yaml), without checking if the config is valid"
     )]
     pub force: bool,
 
@@ -25,30 +28,68 @@ pub struct Lock {
 }
 
 impl SubCommandTrait for Lock {
-    fn run(&self) -> ExitCode {
-        let cazan_json = env::current_dir().unwrap().join("cazan.json");
-        let cazan_directory = env::current_dir().unwrap().join(".cazan");
+    fn run(&self, options: &GlobalOptions) -> ExitCode {
+        let current_dir = env::current_dir().unwrap();
+        let cazan_directory = current_dir.join(".cazan");
         let locked_config_json = cazan_directory.join("config.json");
 
-        if !cazan_json.exists() || !cazan_directory.exists() {
-            ceprintln!("Error cazan is not initialized for this directory");
-            return ExitCode::FAILURE;
-        }
+        let project_file = match ProjectFile::find(&current_dir) {
+            Ok(Some(project_file)) if cazan_directory.exists() => project_file,
+            Ok(_) => {
+                reporter().error("cazan is not initialized for this directory");
+                return ExitCode::FAILURE;
+            }
+            Err(e) => {
+                reporter().error(&e);
+                return ExitCode::FAILURE;
+            }
+        };
+        let file_name = project_file.name();
 
-        let config = match fs::read_to_string(cazan_json.clone()) {
-            Ok(config) => config,
-            Err(_) => {
-                ceprintln!("Error reading cazan.json file");
+        let overlay = match ConfigOverlay::new(options.profile.clone()) {
+            Ok(overlay) => overlay,
+            Err(e) => {
+                reporter().error(&e);
                 return ExitCode::FAILURE;
             }
         };
 
-        let config_string = config.as_str();
+        // The TOML and YAML files are converted to JSON, the locked config is always written in JSON
+        let source = match project_file.read() {
+            Ok(source) => source,
+            Err(ReadError::Io(e)) => {
+                let message = format!("reading {file_name} file: {e}");
+                reporter().error(&message);
+                return ExitCode::FAILURE;
+            }
+            Err(ReadError::Syntax(diagnostic)) => {
+                reporter().diagnostic(&diagnostic);
+                return ExitCode::FAILURE;
+            }
+        };
 
-        let new_checksum = match checksum(&cazan_json) {
-            Ok(checksum) => checksum,
+        // The older configs are upgraded in memory, the newer ones can't be understood
+        let source = match migration::upgrade(source) {
+            Ok((source, None)) => source,
+            Ok((source, Some(version))) => {
+                let message = format!("{file_name} has the config version {version}, use `cazan migrate` to upgrade it to version {CONFIG_VERSION}"
+                );
+                reporter().warning(&message);
+                source
+            }
+            Err(e) => {
+                reporter().error(&e);
+                return ExitCode::FAILURE;
+            }
+        };
+
+        let config_string = source.json.as_str();
+
+        let new_checksum = match checksum(&project_file.path) {
+            Ok(checksum) => overlay.lock_checksum(checksum),
             Err(_) => {
-                ceprintln!("Error calculating checksum of cazan.json");
+                let message = format!("calculating checksum of {file_name}");
+                reporter().error(&message);
                 return ExitCode::FAILURE;
             }
         };
@@ -58,83 +99,116 @@ impl SubCommandTrait for Lock {
         let old_checksum = fs::read_to_string(checksum_file.clone()).unwrap_or_default();
 
         if old_checksum.is_empty() && checksum_file.exists() {
-            ceprintln!("Error reading checksum file");
+            reporter().error("reading checksum file");
             return ExitCode::FAILURE;
         }
 
+        let locked_message = match &overlay.profile {
+            Some(profile) => format!("config with the profile `{profile}`"),
+            None => "config".to_string(),
+        };
+
         if self.force {
-            if fs::copy(cazan_json.clone(), locked_config_json).is_err() {
-                ceprintln!("Error copying cazan.json file to .cazan/config.json");
+            // The profile and the overrides are applied even if the config is invalid, as long as it is valid JSON
+            let resolved = match overlay.apply(config_string) {
+                Ok(resolved) => resolved,
+                Err(e) => {
+                    reporter().error(&e);
+                    return ExitCode::FAILURE;
+                }
+            };
+
+            if fs::write(&locked_config_json, resolved).is_err() {
+                let message = format!("copying {file_name} file to .cazan/config.json");
+                reporter().error(&message);
                 return ExitCode::FAILURE;
             }
 
             if fs::write(checksum_file, new_checksum).is_err() {
-                ceprintln!("Error saving checksum");
+                reporter().error("saving checksum");
                 return ExitCode::FAILURE;
             }
 
-            cprintln!("Locked config");
+            reporter().status("Locked", &locked_message);
             return ExitCode::SUCCESS;
         }
 
         if old_checksum == new_checksum {
-            cprintln!("Already up-to-date");
+            reporter().status("Already", "up-to-date");
             return ExitCode::SUCCESS;
         }
 
-        let deserializer = &mut serde_json::Deserializer::from_str(config_string);
-        let mut unused: Vec<String> = vec![];
+        let (config, unused) = match Config::parse(config_string) {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                reporter().diagnostic(&Diagnostic::from_json_error(&source, &e));
+                return ExitCode::FAILURE;
+            }
+        };
 
-        let config: Config = match serde_ignored::deserialize(deserializer, |field| {
-            unused.push(field.to_string())
-        }) {
-            Ok(config) => config,
-            Err(_) => {
-                ceprintln!("Error cazan.json is invalid");
+        let errors = config.validate();
+        if !errors.is_empty() {
+            for error in errors {
+                reporter().diagnostic(&Diagnostic::invalid_value(&source, &error));
+            }
+            return ExitCode::FAILURE;
+        }
+
+        let resolved = match overlay.apply(config_string) {
+            Ok(resolved) => resolved,
+            Err(e) => {
+                reporter().error(&e);
                 return ExitCode::FAILURE;
             }
         };
 
         if self.allow_unknown {
-            if fs::copy(cazan_json.clone(), locked_config_json).is_err() {
-                ceprintln!("Error copying cazan.json file to .cazan/config.json");
+            if fs::write(&locked_config_json, resolved).is_err() {
+                let message = format!("copying {file_name} file to .cazan/config.json");
+                reporter().error(&message);
                 return ExitCode::FAILURE;
             }
 
             if fs::write(checksum_file, new_checksum).is_err() {
-                ceprintln!("Error saving checksum");
+                reporter().error("saving checksum");
                 return ExitCode::FAILURE;
             }
 
-            cprintln!("Locked config");
+            reporter().status("Locked", &locked_message);
             return ExitCode::SUCCESS;
         }
 
         if !unused.is_empty() {
-            let warning = format!(
-                "Warning unknown fields ({}) are ignored. To force using them, use --allow-unknown",
-                unused
-                    .iter()
-                    .map(|field| format!("`{}`", field))
-                    .collect::<Vec<_>>()
-                    .join(", ")
-            );
-            cprintln!(warning => Yellow);
+            for field in &unused {
+                reporter().diagnostic(&Diagnostic::unknown_field(Level::Warning, &source, field));
+            }
+            reporter()
+                .warning("unknown fields are ignored. To force using them, use --allow-unknown");
         }
 
+        // The profile and the overrides are checked on their own, a resolved config that can't be parsed is reported
+        let resolved = ConfigSource::unsaved(file_name, resolved);
+        let config = match Config::parse(&resolved.json) {
+            Ok((config, _)) => config,
+            Err(e) => {
+                reporter().diagnostic(&Diagnostic::from_json_error(&resolved, &e));
+                return ExitCode::FAILURE;
+            }
+        };
         let config = serde_json::to_string_pretty(&config).unwrap();
 
         if fs::write(locked_config_json, config).is_err() {
-            ceprintln!("Error copying cazan.json to .cazan/config.json");
+            let message = format!("copying {file_name} to .cazan/config.json");
+            reporter().error(&message);
             return ExitCode::FAILURE;
         }
 
         if fs::write(checksum_file, new_checksum).is_err() {
-            ceprintln!("Error saving checksum");
+            reporter().error("saving checksum");
             return ExitCode::FAILURE;
         }
 
-        cprintln!("Locked config");
+        reporter().status("Locked", &locked_message);
 
         ExitCode::SUCCESS
     }
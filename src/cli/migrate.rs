@@ -0,0 +1,71 @@
+//! The `migrate` subcommand
+//! This command upgrades the project file to the config version of this version of cazan, it shows the changes as a
+//! diff before rewriting the file
+
+use std::process::ExitCode;
+
+use super::config::EditedFile;
+use super::{GlobalOptions, SubCommandTrait};
+use crate::migration::{self, CONFIG_VERSION};
+
+use crate::report::reporter;
+use argh::FromArgs;
+
+#[derive(PartialEq, Debug, FromArgs)]
+#[argh(
+    subcommand,
+    name = "migrate",
+    description = "upgrade the project file to the config version of this version of cazan"
+)]
+pub struct Migrate {
+    #[argh(switch, description = "only show the diff, the file isn't rewritten")]
+    pub dry_run: bool,
+
+    #[argh(switch, description = "lock the config afterwards, without asking")]
+    pub lock: bool,
+}
+
+impl SubCommandTrait for Migrate {
+    fn run(&self, options: &GlobalOptions) -> ExitCode {
+        let Some(file) = EditedFile::open() else {
+            return ExitCode::FAILURE;
+        };
+        let file_name = file.name();
+
+        let mut migrated = file.value.clone();
+        let applied = match migration::migrate(&mut migrated, file_name) {
+            Ok(applied) => applied,
+            Err(e) => {
+                reporter().error(&e);
+                return ExitCode::FAILURE;
+            }
+        };
+
+        if migrated == file.value {
+            let message = format!("{file_name} is already at the config version {CONFIG_VERSION}");
+            reporter().status("Up-to-date", &message);
+            return ExitCode::SUCCESS;
+        }
+
+        let text = match file.to_text(&migrated) {
+            Ok(text) => text,
+            Err(e) => {
+                let message = format!("writing {file_name}: {e}");
+                reporter().error(&message);
+                return ExitCode::FAILURE;
+            }
+        };
+
+        for description in applied {
+            reporter().status("Migration", description);
+        }
+        reporter().diff(file_name, &file.text, &text);
+
+        if self.dry_run {
+            return ExitCode::SUCCESS;
+        }
+
+        let message = format!("{file_name} to the config version {CONFIG_VERSION}");
+        file.save(migrated, options, self.lock, "Migrated", &message)
+    }
+}
@@ -1,7 +1,21 @@
+mod check;
+mod config;
 mod init;
 mod lock;
+mod migrate;
 mod prebuild;
+mod schema;
 mod subcommands;
+mod watch;
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use crate::project::{self, Format};
+use crate::report::MessageFormat;
+use crate::terminal::ColorChoice;
 
 use argh::FromArgs;
 pub use subcommands::{SubCommandEnum, SubCommandTrait};
@@ -9,9 +23,205 @@ pub use subcommands::{SubCommandEnum, SubCommandTrait};
 #[derive(FromArgs, Debug)]
 #[argh(description = "Cazan CLI")]
 pub(crate) struct Cli {
-    #[argh(switch, short = 'v', description = "print version info")]
+    #[argh(
+        switch,
+        short = 'V',
+        description = "print version info (its short form was -v before -v became --verbose)"
+    )]
     pub(crate) version: bool,
 
+    #[argh(switch, short = 'q', description = "only print the errors")]
+    pub(crate) quiet: bool,
+
+    #[argh(
+        switch,
+        short = 'v',
+        description = "print the timings of each asset, -vv also prints the files matched by the globs and the resolved config"
+    )]
+    pub(crate) verbose: u8,
+
+    #[argh(
+        option,
+        description = "profile of the config to apply, from its `profiles` section"
+    )]
+    pub(crate) profile: Option<String>,
+
+    #[argh(
+        option,
+        short = 'C',
+        description = "run as if cazan was started in this directory"
+    )]
+    pub(crate) directory: Option<PathBuf>,
+
+    #[argh(
+        option,
+        description = "path of the project file (cazan.json, cazan.toml or cazan.yaml), instead of looking for it in the parent directories"
+    )]
+    pub(crate) manifest_path: Option<PathBuf>,
+
+    #[argh(
+        option,
+        description = "format of the messages: human (default), or json for one JSON event per line on stdout"
+    )]
+    pub(crate) message_format: Option<MessageFormat>,
+
+    #[argh(
+        option,
+        description = "when to colour the output: auto (default, not when NO_COLOR is set or stdout isn't a terminal), always or never"
+    )]
+    pub(crate) color: Option<ColorChoice>,
+
     #[argh(subcommand)]
     pub subcommand: Option<SubCommandEnum>,
 }
+
+impl Cli {
+    /// Parse the arguments of the process like `argh::from_env`, the repeated short switches (`-vv`) are accepted
+    pub fn from_env() -> Self {
+        let args: Vec<String> = env::args().collect();
+        let command = Path::new(&args[0])
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or(&args[0]);
+
+        let args = expand_verbosity(&args[1..]);
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+
+        Self::from_args(&[command], &args).unwrap_or_else(|early_exit| match early_exit.status {
+            Ok(()) => {
+                println!("{}", early_exit.output);
+                process::exit(0)
+            }
+            Err(()) => {
+                eprintln!(
+                    "{}\nRun {command} --help for more information.",
+                    early_exit.output
+                );
+                process::exit(1)
+            }
+        })
+    }
+}
+
+/// Split the `-vv…` arguments into repeated `-v` switches, which argh counts. The arguments after `--` are kept as is
+fn expand_verbosity(args: &[String]) -> Vec<String> {
+    let end = args
+        .iter()
+        .position(|arg| arg == "--")
+        .unwrap_or(args.len());
+    let (options, rest) = args.split_at(end);
+
+    options
+        .iter()
+        .flat_map(|arg| match arg.strip_prefix('-') {
+            Some(switches) if switches.len() > 1 && switches.chars().all(|c| c == 'v') => {
+                vec!["-v".to_string(); switches.len()]
+            }
+            _ => vec![arg.clone()],
+        })
+        .chain(rest.iter().cloned())
+        .collect()
+}
+
+/// The options given before the subcommand, shared by all of them
+pub struct GlobalOptions {
+    pub profile: Option<String>,
+    pub directory: Option<PathBuf>,
+    pub manifest_path: Option<PathBuf>,
+}
+
+impl GlobalOptions {
+    /// The command locking the project with the selected profile, for the hints
+    pub fn lock_command(&self) -> String {
+        match &self.profile {
+            Some(profile) => format!("cazan --profile {profile} lock"),
+            None => "cazan lock".to_string(),
+        }
+    }
+
+    /// Move to the root of the project, so that the subcommands and the asset globs are relative to it
+    /// `-C` is applied first, then the root is the directory of `--manifest-path`, or the closest directory with a
+    /// project file when `search` is true. `init` doesn't search, the project is created in the current directory
+    pub fn enter_project(&self, search: bool) -> Result<(), String> {
+        if let Some(directory) = &self.directory {
+            env::set_current_dir(directory).map_err(|e| {
+                format!(
+                    "couldn't change to the directory `{}`: {e}",
+                    directory.display()
+                )
+            })?;
+        }
+
+        let root = match &self.manifest_path {
+            Some(manifest_path) => {
+                if Format::from_file_name(manifest_path).is_none() {
+                    return Err(format!(
+                        "`{}` is not a project file, its name must be cazan.json, cazan.toml or cazan.yaml",
+                        manifest_path.display()
+                    ));
+                }
+                if search && !manifest_path.is_file() {
+                    return Err(format!("`{}` doesn't exist", manifest_path.display()));
+                }
+                let root = manifest_path.parent().unwrap_or(Path::new(""));
+                // `init` creates the directory of the new project
+                if !search && !root.as_os_str().is_empty() {
+                    fs::create_dir_all(root).map_err(|e| {
+                        format!("couldn't create the directory `{}`: {e}", root.display())
+                    })?;
+                }
+                Some(root.to_path_buf())
+            }
+            None if search => {
+                let current_dir = env::current_dir()
+                    .map_err(|e| format!("couldn't read the current directory: {e}"))?;
+                project::find_root(&current_dir).map(Path::to_path_buf)
+            }
+            None => None,
+        };
+
+        match root {
+            Some(root) if !root.as_os_str().is_empty() => env::set_current_dir(&root)
+                .map_err(|e| format!("couldn't change to the directory `{}`: {e}", root.display())),
+            _ => Ok(()),
+        }
+    }
+
+    /// The format of the project file given with `--manifest-path`
+    pub fn manifest_format(&self) -> Option<Format> {
+        self.manifest_path
+            .as_deref()
+            .and_then(Format::from_file_name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand(args: &[&str]) -> Vec<String> {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        expand_verbosity(&args)
+    }
+
+    #[test]
+    fn only_the_repeated_v_switches_are_expanded() {
+        assert_eq!(
+            expand(&["-vvv", "prebuild"]),
+            ["-v", "-v", "-v", "prebuild"]
+        );
+        assert_eq!(expand(&["-v", "-V"]), ["-v", "-V"]);
+        assert_eq!(
+            expand(&["-vq", "--vv", "-", "-vV"]),
+            ["-vq", "--vv", "-", "-vV"]
+        );
+    }
+
+    #[test]
+    fn the_arguments_after_the_separator_are_kept() {
+        assert_eq!(
+            expand(&["-vv", "config", "set", "--", "name", "-vv"]),
+            ["-v", "-v", "config", "set", "--", "name", "-vv"]
+        );
+    }
+}
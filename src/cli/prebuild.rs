@@ -1,29 +1,38 @@
 //! The `prebuild` subcommand
 //! This command is used to prebuild the assets of your project
-//! It builds the PNG assets by reading PNG files, extracting the edges, simplifying the edges, and writing the edges to a JSON file
+//! It builds the image assets (PNG, WebP, GIF... with an alpha channel) by reading the images, extracting the edges, simplifying the edges, and writing the edges to a JSON file
+//! The results are cached in `.cazan/build/cache.json`, so only new or modified assets are parsed again
 
+use std::collections::{BTreeMap, HashMap};
 use std::error::Error;
 use std::fs;
+use std::num::NonZeroUsize;
 use std::path::{Path, PathBuf};
 use std::process::ExitCode;
-use std::sync::{Arc, Mutex};
-
-use super::SubCommandTrait;
-use crate::config::{checksum, Config};
-use crate::terminal::SubTerminal;
-
-use cazan_common::geometry::Triangle;
-use cazan_common::rdp::rdp;
-use cazan_common::{image::ImageEdgesParser, triangulation::triangulate};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::Instant;
+
+use super::{GlobalOptions, SubCommandTrait};
+use crate::cache::PreBuildCache;
+use crate::config::{checksum, AssetConfig, Config, ConfigOverlay};
+use crate::diagnostic::Diagnostic;
+use crate::hitbox::{self, Hitbox, HitboxError, HitboxSettings, Shape, Stats};
+use crate::project::{ConfigSource, ProjectFile};
+use crate::report::{reporter, AssetResult, Summary};
+use crate::sheet::{self, Slicing};
 
 use argh::FromArgs;
-use cprint::{ceprintln, cformat, cprintln};
-use glob::glob;
+use glob::{glob, Pattern, PatternError};
 use image::GenericImageView;
 use plotters::prelude::*;
 use serde_json::{json, Value};
 
-const DEFAULT_EPSILON: f64 = 3.0;
+pub(crate) const DEFAULT_EPSILON: f64 = 3.0;
+/// Colours of the shapes in the preview files, each component of an asset gets its own
+const PREVIEW_COLORS: [RGBColor; 6] = [RED, BLUE, GREEN, MAGENTA, CYAN, YELLOW];
+/// Exit code when some assets failed and the others were parsed, their hitboxes are only written with `--keep-going`
+/// The exit code is 1 when all the assets parsed by the run failed, whatever was found in the cache
+const EXIT_PARTIAL_FAILURE: u8 = 2;
 
 #[derive(PartialEq, Debug, FromArgs)]
 #[argh(
@@ -46,6 +55,38 @@ pub struct PreBuild {
     )]
     pub epsilon: Option<f64>,
 
+    #[argh(
+        option,
+        description = "pixels with an alpha lower or equal to this value are transparent (0-255, default: 0)"
+    )]
+    pub alpha_threshold: Option<u8>,
+
+    #[argh(
+        option,
+        description = "number of pixels removed from the borders of the opaque shapes (erosion)"
+    )]
+    pub erode: Option<u32>,
+
+    #[argh(
+        option,
+        description = "number of pixels added around the opaque shapes, after the erosion (dilation)"
+    )]
+    pub dilate: Option<u32>,
+
+    #[argh(
+        option,
+        short = 'j',
+        description = "number of assets parsed in parallel (default: number of CPUs)"
+    )]
+    pub jobs: Option<usize>,
+
+    #[argh(
+        switch,
+        short = 'k',
+        description = "write the hitboxes of the valid assets even if some assets fail (exits with 2 either way)"
+    )]
+    pub keep_going: bool,
+
     #[argh(
         switch,
         short = 'p',
@@ -61,88 +102,145 @@ pub struct PreBuild {
 }
 
 impl SubCommandTrait for PreBuild {
-    fn run(&self) -> ExitCode {
+    fn run(&self, options: &GlobalOptions) -> ExitCode {
+        let start = Instant::now();
+
         if self.open && !self.preview {
-            cprintln!("Warning use of `--open` without `--preview` is useless" => Yellow);
+            reporter().warning("use of `--open` without `--preview` is useless");
+        }
+
+        if self.jobs == Some(0) {
+            reporter().error("--jobs must be at least 1");
+            return ExitCode::FAILURE;
         }
 
         let current_dir = std::env::current_dir().unwrap();
         let cazan_directory = current_dir.join(".cazan");
         if !cazan_directory.exists() {
-            ceprintln!("Error cazan is not initialized for this directory");
+            reporter().error("cazan is not initialized for this directory");
             return ExitCode::FAILURE;
         }
 
-        let cazan_config = current_dir.join("cazan.json");
         let checksum_file = current_dir.join(".cazan/checksum.txt");
-        let config = fs::read_to_string(current_dir.join(".cazan/config.json")).unwrap();
-        let config: Config = serde_json::from_str(config.as_str()).unwrap();
+        let Ok(config) = fs::read_to_string(current_dir.join(".cazan/config.json")) else {
+            reporter().error("reading the locked config, use `cazan lock` to create it");
+            return ExitCode::FAILURE;
+        };
+        // The unknown fields were reported by `cazan lock`
+        let source = ConfigSource::json(".cazan/config.json", config);
+        let mut config = match Config::parse(&source.json) {
+            Ok((config, _)) => config,
+            Err(e) => {
+                reporter().diagnostic(&Diagnostic::from_json_error(&source, &e));
+                return ExitCode::FAILURE;
+            }
+        };
 
-        if checksum(&cazan_config).unwrap() != fs::read_to_string(checksum_file).unwrap_or_default()
-        {
-            cprintln!("Warning lock file is not up-to-date with cazan.json. To update it use `cazan lock`" => Yellow);
+        // The profile and the overrides were applied by `cazan lock`, the assets aren't built with other ones
+        let overlay = match ConfigOverlay::new(options.profile.clone()) {
+            Ok(overlay) => overlay,
+            Err(e) => {
+                reporter().error(&e);
+                return ExitCode::FAILURE;
+            }
+        };
+        let locked_checksum = fs::read_to_string(checksum_file).unwrap_or_default();
+        if !overlay.is_locked(&locked_checksum) {
+            let message = format!(
+                "the config was locked with another profile or other `CAZAN_*` overrides, use `{}` to lock it with the selected ones",
+                options.lock_command()
+            );
+            reporter().error(&message);
+            return ExitCode::FAILURE;
+        }
+        if let Ok(Some(project_file)) = ProjectFile::find(&current_dir) {
+            if checksum(&project_file.path)
+                .ok()
+                .map(|checksum| overlay.lock_checksum(checksum))
+                != Some(locked_checksum)
+            {
+                let message = format!(
+                    "lock file is not up-to-date with {}. To update it use `{}`",
+                    project_file.name(),
+                    options.lock_command()
+                );
+                reporter().warning(&message);
+            }
         }
 
-        let assets = if self.assets.is_empty() {
-            config.assets.unwrap_or_default()
-        } else {
-            self.assets.iter().map(|s| s.as_str()).collect()
+        // The command line wins over the locked config
+        if !self.assets.is_empty() {
+            config.assets = Some(
+                self.assets
+                    .iter()
+                    .cloned()
+                    .map(AssetConfig::Pattern)
+                    .collect(),
+            );
+        }
+        config.rdp_epsilon = self.epsilon.or(config.rdp_epsilon);
+        config.alpha_threshold = self.alpha_threshold.or(config.alpha_threshold);
+        config.erode = self.erode.or(config.erode);
+        config.dilate = self.dilate.or(config.dilate);
+        reporter().resolved_config(&config);
+
+        let assets = config.assets.clone().unwrap_or_default();
+
+        let rules = match asset_rules(&assets, &config, self.overrides()) {
+            Ok(rules) => rules,
+            Err(e) => {
+                let message = format!("invalid asset pattern: {e}");
+                reporter().error(&message);
+                return ExitCode::FAILURE;
+            }
         };
 
-        let files: Vec<PathBuf> = assets
-            .iter()
-            .flat_map(|pattern| glob(pattern).expect("Failed to read pattern"))
-            .map(|entry| entry.unwrap_or_else(|_| PathBuf::new()))
-            .filter(|file| file.extension().map_or(false, |ext| ext == "png"))
-            .collect();
+        let files = asset_files(&rules);
+        let total = files.len();
 
         if files.is_empty() {
+            reporter().summary(&Summary {
+                assets: 0,
+                parsed: 0,
+                cached: 0,
+                failed: 0,
+                duration: start.elapsed(),
+            });
             return ExitCode::SUCCESS;
         }
 
-        let mut map = serde_json::Map::<String, Value>::new();
-        let terminal: Arc<Mutex<SubTerminal>> =
-            Arc::new(Mutex::new(SubTerminal::new(files.len() as u16)));
+        let cazan_build_directory = cazan_directory.join("build");
+        let cache_file = cazan_build_directory.join("cache.json");
+        let mut cache = PreBuildCache::load(&cache_file);
+
+        let (parsed, failed) =
+            parse_assets(files, self.jobs.unwrap_or_else(default_jobs), &mut cache);
 
-        let handles: Vec<_> = files
-            .iter()
-            .enumerate()
-            .map(|(i, file)| {
-                let epsilon = self.epsilon;
-                let file = file.clone();
-                let terminal = terminal.clone();
-                std::thread::spawn(move || {
-                    terminal.lock().unwrap().write_to(
-                        cformat!("Parsing", file.to_str().unwrap() => Cyan).as_str(),
-                        i,
-                    );
+        for failure in &failed {
+            failure.report();
+        }
 
-                    let image = image::open(&file).unwrap();
-                    let edges_parser = ImageEdgesParser::new(image);
-                    let polygon = edges_parser.as_polygon();
-                    let rdp_polygon = rdp(
-                        &polygon,
-                        epsilon.unwrap_or(config.rdp_epsilon.unwrap_or(DEFAULT_EPSILON)),
-                    );
-                    let triangles = triangulate(&rdp_polygon).expect("Error triangulating");
-
-                    terminal.lock().unwrap().rewrite_to(
-                        cformat!(
-                            "Parsed",
-                            format!(
-                                "`{}` to {} triangles",
-                                file.file_name().unwrap().to_str().unwrap().to_string(),
-                                triangles.len()
-                            )
-                        )
-                        .as_ref(),
-                        i,
-                    );
+        if !failed.is_empty() {
+            let message = format!("{} of {} assets failed", failed.len(), total);
+            reporter().error(&message);
+        }
 
-                    (file, triangles)
-                })
-            })
-            .collect();
+        let cached = parsed.iter().filter(|asset| asset.cached).count();
+        reporter().summary(&Summary {
+            assets: total,
+            parsed: parsed.len() - cached,
+            cached,
+            failed: failed.len(),
+            duration: start.elapsed(),
+        });
+
+        if !failed.is_empty() && (parsed.is_empty() || !self.keep_going) {
+            // The successfully parsed assets are cached, so they won't be parsed again
+            if cache.save(&cache_file).is_err() {
+                reporter().warning("couldn't save the prebuild cache");
+            }
+            return exit_code(&parsed, failed.len());
+        }
 
         let cazan_tmp: PathBuf = if self.preview {
             let c = std::env::current_dir().unwrap().join(".cazan-tmp");
@@ -156,57 +254,397 @@ impl SubCommandTrait for PreBuild {
 
         let mut warnings: Vec<String> = vec![];
 
-        for handle in handles {
-            let (file, triangles) = handle.join().unwrap();
-
-            if self.preview && file.extension() == Some("png".as_ref()) {
-                match preview(&file, &triangles, &cazan_tmp) {
-                    Ok(_) => {}
-                    Err(e) => warnings.push(format!(
-                        "Warning `{}` preview couldn't have been created: {e}",
-                        file.to_str().unwrap()
-                    )),
+        if self.preview {
+            for asset in &parsed {
+                if let Err(e) = preview(asset, &cazan_tmp) {
+                    warnings.push(format!(
+                        "`{}` preview couldn't have been created: {e}",
+                        asset.file.to_str().unwrap()
+                    ));
                 }
             }
-
-            map.insert(checksum(&file).unwrap().to_string(), json!(triangles));
         }
 
-        terminal.lock().unwrap().move_to_last_line_and_new_line();
-
         for warning in warnings {
-            cprintln!(warning => Yellow);
+            reporter().warning(&warning);
         }
 
-        let cazan_build_directory = cazan_directory.join("build");
+        if write_assets(&cazan_build_directory, &parsed).is_err() {
+            reporter().error("writing `.cazan/build/assets.json`");
+            return ExitCode::FAILURE;
+        }
 
-        if !cazan_build_directory.exists() && fs::create_dir(cazan_build_directory.clone()).is_err()
-        {
-            ceprintln!("Error creating `.cazan/build` directory")
+        // Entries of other assets are kept when only a subset of the assets is prebuilt
+        if self.assets.is_empty() {
+            cache.prune();
         }
 
-        let mut writer = fs::File::create(cazan_build_directory.join("assets.json")).unwrap();
-        serde_json::to_writer(&mut writer, &map).unwrap();
+        if cache.save(&cache_file).is_err() {
+            reporter().warning("couldn't save the prebuild cache");
+        }
 
         if self.preview && self.open {
             open::that(cazan_tmp).expect("Couldn't open file explorer");
         }
-        ExitCode::SUCCESS
+
+        exit_code(&parsed, failed.len())
+    }
+}
+
+impl PreBuild {
+    fn overrides(&self) -> SettingsOverrides {
+        SettingsOverrides {
+            epsilon: self.epsilon,
+            alpha_threshold: self.alpha_threshold,
+            erode: self.erode,
+            dilate: self.dilate,
+        }
     }
 }
 
-fn preview(
-    file: &PathBuf,
-    triangles: &Vec<Triangle>,
-    cazan_tmp: &Path,
+/// An asset parsed by the prebuild pipeline
+pub(crate) struct ParsedAsset {
+    pub file: PathBuf,
+    pub checksum: String,
+    pub settings: HitboxSettings,
+    pub hitbox: Hitbox,
+    /// The hitbox comes from the cache, the asset wasn't parsed again
+    pub cached: bool,
+}
+
+/// An asset whose hitbox couldn't be computed
+pub(crate) struct FailedAsset {
+    pub file: PathBuf,
+    pub error: HitboxError,
+}
+
+impl FailedAsset {
+    pub fn report(&self) {
+        let message = format!(
+            "`{}` couldn't be parsed: {}",
+            self.file.to_str().unwrap(),
+            self.error
+        );
+        reporter().error(&message);
+    }
+}
+
+/// An entry of the assets with the hitbox settings of the files it matches, `None` if they are skipped
+pub(crate) struct AssetRule {
+    pub pattern: Pattern,
+    pub settings: Option<HitboxSettings>,
+}
+
+/// Hitbox settings given on the command line, they take precedence over the config
+#[derive(Default, Clone, Copy)]
+pub(crate) struct SettingsOverrides {
+    pub epsilon: Option<f64>,
+    pub alpha_threshold: Option<u8>,
+    pub erode: Option<u32>,
+    pub dilate: Option<u32>,
+}
+
+/// Resolve the hitbox settings of each asset entry
+/// The command line overrides win over the settings of the entries, which win over the top-level settings
+pub(crate) fn asset_rules(
+    assets: &[AssetConfig],
+    config: &Config,
+    overrides: SettingsOverrides,
+) -> Result<Vec<AssetRule>, PatternError> {
+    let defaults = HitboxSettings {
+        epsilon: config.rdp_epsilon.unwrap_or(DEFAULT_EPSILON),
+        alpha_threshold: config.alpha_threshold.unwrap_or(0),
+        erode: config.erode.unwrap_or(0),
+        dilate: config.dilate.unwrap_or(0),
+        slicing: None,
+    };
+
+    assets
+        .iter()
+        .map(|asset| {
+            let settings = match asset {
+                AssetConfig::Pattern(_) => Some(HitboxSettings {
+                    epsilon: overrides.epsilon.unwrap_or(defaults.epsilon),
+                    alpha_threshold: overrides
+                        .alpha_threshold
+                        .unwrap_or(defaults.alpha_threshold),
+                    erode: overrides.erode.unwrap_or(defaults.erode),
+                    dilate: overrides.dilate.unwrap_or(defaults.dilate),
+                    slicing: None,
+                }),
+                AssetConfig::Detailed(asset) if asset.skip => None,
+                AssetConfig::Detailed(asset) => Some(HitboxSettings {
+                    epsilon: overrides
+                        .epsilon
+                        .or(asset.epsilon)
+                        .unwrap_or(defaults.epsilon),
+                    alpha_threshold: overrides
+                        .alpha_threshold
+                        .or(asset.alpha_threshold)
+                        .unwrap_or(defaults.alpha_threshold),
+                    erode: overrides.erode.or(asset.erode).unwrap_or(defaults.erode),
+                    dilate: overrides.dilate.or(asset.dilate).unwrap_or(defaults.dilate),
+                    slicing: asset.frames.as_ref().map(|frames| match &frames.sidecar {
+                        Some(sidecar) => Slicing::Sidecar(sidecar.clone()),
+                        None => Slicing::Grid {
+                            width: frames.width.unwrap_or(0),
+                            height: frames.height.unwrap_or(0),
+                            margin: frames.margin,
+                            spacing: frames.spacing,
+                            count: frames.count,
+                        },
+                    }),
+                }),
+            };
+
+            Ok(AssetRule {
+                pattern: Pattern::new(asset.pattern())?,
+                settings,
+            })
+        })
+        .collect()
+}
+
+/// Hitbox settings of a file, the last matching entry wins
+pub(crate) fn settings_for(rules: &[AssetRule], file: &Path) -> Option<HitboxSettings> {
+    rules
+        .iter()
+        .rev()
+        .find(|rule| rule.pattern.matches_path(file))
+        .and_then(|rule| rule.settings.clone())
+}
+
+/// Expand the glob patterns of the assets to the list of the image files with their hitbox settings
+/// When a file is matched by several entries, the last one wins
+pub(crate) fn asset_files(rules: &[AssetRule]) -> Vec<(PathBuf, HitboxSettings)> {
+    let mut files: Vec<(PathBuf, Option<HitboxSettings>)> = vec![];
+    let mut indexes: HashMap<PathBuf, usize> = HashMap::new();
+
+    for rule in rules {
+        let entries = glob(rule.pattern.as_str()).expect("Failed to read pattern");
+        let matched: Vec<PathBuf> = entries
+            .filter_map(Result::ok)
+            .filter(|file| file.is_file())
+            .collect();
+        reporter().pattern_expanded(rule.pattern.as_str(), &matched);
+
+        for file in matched {
+            match indexes.get(&file) {
+                Some(&i) => files[i].1 = rule.settings.clone(),
+                None => {
+                    indexes.insert(file.clone(), files.len());
+                    files.push((file, rule.settings.clone()));
+                }
+            }
+        }
+    }
+
+    // The skipped files are only warned about once, whatever the number of entries matching them
+    files
+        .into_iter()
+        .filter_map(|(file, settings)| Some((file, settings?)))
+        .filter(|(file, _)| is_parsable(file))
+        .collect()
+}
+
+/// Whether a matched file is an image in a supported format, a warning tells why the others are skipped
+pub(crate) fn is_parsable(file: &Path) -> bool {
+    let Some(reason) = hitbox::unsupported_reason(file) else {
+        return true;
+    };
+    let warning = format!("`{}` is skipped, {reason}", file.display());
+    reporter().warning(&warning);
+    false
+}
+
+/// Parse the hitboxes of the files, reusing the cached ones and caching the new ones
+pub(crate) fn parse_assets(
+    files: Vec<(PathBuf, HitboxSettings)>,
+    jobs: usize,
+    cache: &mut PreBuildCache,
+) -> (Vec<ParsedAsset>, Vec<FailedAsset>) {
+    let mut parsed: Vec<ParsedAsset> = vec![];
+    let mut failed: Vec<FailedAsset> = vec![];
+    // The cache key is kept to insert the new hitboxes
+    let mut to_parse: Vec<(PathBuf, String, HitboxSettings, String)> = vec![];
+
+    for (file, settings) in files {
+        let checksum = match checksum(&file) {
+            Ok(checksum) => checksum,
+            Err(e) => {
+                failed.push(FailedAsset {
+                    file,
+                    error: e.into(),
+                });
+                continue;
+            }
+        };
+        // The frames of a sprite sheet change with its sidecar
+        let sidecar_checksum = match &settings.slicing {
+            Some(Slicing::Sidecar(sidecar)) => {
+                match crate::config::checksum(&sheet::sidecar_path(&file, sidecar)) {
+                    Ok(checksum) => Some(checksum),
+                    Err(e) => {
+                        failed.push(FailedAsset {
+                            file,
+                            error: HitboxError::Frames(format!("couldn't read the sidecar: {e}")),
+                        });
+                        continue;
+                    }
+                }
+            }
+            _ => None,
+        };
+
+        let key = PreBuildCache::key(&checksum, &settings, sidecar_checksum.as_deref());
+        match cache.get(&key) {
+            Some(hitbox) => {
+                reporter().asset_finished(&file, &checksum, AssetResult::Cached(hitbox));
+                parsed.push(ParsedAsset {
+                    file,
+                    checksum,
+                    settings,
+                    hitbox: hitbox.clone(),
+                    cached: true,
+                })
+            }
+            // Only the rebuilt images are opened, a cached hitbox was computed from an image with an alpha channel
+            None => match hitbox::has_alpha_channel(&file) {
+                Ok(true) => to_parse.push((file, checksum, settings, key)),
+                Ok(false) => {
+                    let warning = format!(
+                        "`{}` is skipped, the image has no alpha channel",
+                        file.display()
+                    );
+                    reporter().warning(&warning);
+                }
+                Err(error) => failed.push(FailedAsset { file, error }),
+            },
+        }
+    }
+
+    if !parsed.is_empty() {
+        let message = format!("{} unchanged assets from the cache", parsed.len());
+        reporter().status("Reusing", &message);
+    }
+
+    if to_parse.is_empty() {
+        return (parsed, failed);
+    }
+
+    let total = to_parse.len();
+    let workers = jobs.clamp(1, total);
+    reporter().parsing_started(total, workers);
+
+    let queue = Arc::new(Mutex::new(to_parse.into_iter()));
+    let (sender, receiver) = mpsc::channel();
+
+    let handles: Vec<_> = (0..workers)
+        .map(|_| {
+            let queue = queue.clone();
+            let sender = sender.clone();
+            std::thread::spawn(move || loop {
+                let Some((file, checksum, settings, key)) = queue.lock().unwrap().next() else {
+                    break;
+                };
+
+                reporter().asset_started(&file);
+
+                let mut stats = Stats::default();
+                let result = hitbox::compute(&file, &settings, &mut stats);
+
+                let asset_result = match &result {
+                    Ok(hitbox) => AssetResult::Parsed(hitbox, &stats),
+                    Err(error) => AssetResult::Failed(error),
+                };
+                reporter().asset_finished(&file, &checksum, asset_result);
+
+                sender
+                    .send(match result {
+                        Ok(hitbox) => Ok((
+                            key,
+                            ParsedAsset {
+                                file,
+                                checksum,
+                                settings,
+                                hitbox,
+                                cached: false,
+                            },
+                        )),
+                        Err(error) => Err(FailedAsset { file, error }),
+                    })
+                    .unwrap();
+            })
+        })
+        .collect();
+
+    drop(sender);
+
+    for result in receiver {
+        match result {
+            Ok((key, asset)) => {
+                cache.insert(key, asset.hitbox.clone());
+                parsed.push(asset);
+            }
+            Err(failure) => failed.push(failure),
+        }
+    }
+
+    for handle in handles {
+        handle.join().unwrap();
+    }
+
+    reporter().parsing_finished();
+
+    (parsed, failed)
+}
+
+/// 0 when all the assets were parsed, `EXIT_PARTIAL_FAILURE` when only some of them were, 1 when none was
+/// Only the assets parsed by this run count, a failure is total when the others all come from the cache
+fn exit_code(parsed: &[ParsedAsset], failed: usize) -> ExitCode {
+    let parsed = parsed.iter().filter(|asset| !asset.cached).count();
+    match (parsed, failed) {
+        (_, 0) => ExitCode::SUCCESS,
+        (0, _) => ExitCode::FAILURE,
+        _ => ExitCode::from(EXIT_PARTIAL_FAILURE),
+    }
+}
+
+/// Number of worker threads used when `--jobs` isn't given
+pub(crate) fn default_jobs() -> usize {
+    std::thread::available_parallelism()
+        .map(NonZeroUsize::get)
+        .unwrap_or(1)
+}
+
+/// Write the hitboxes to `.cazan/build/assets.json`, keyed by the checksum of the assets
+/// The keys are sorted so that the file doesn't change when the assets are parsed in another order
+pub(crate) fn write_assets<'a>(
+    cazan_build_directory: &Path,
+    assets: impl IntoIterator<Item = &'a ParsedAsset>,
 ) -> Result<(), Box<dyn Error>> {
+    if !cazan_build_directory.exists() {
+        fs::create_dir(cazan_build_directory)?;
+    }
+
+    let map: BTreeMap<String, Value> = assets
+        .into_iter()
+        .map(|asset| (asset.checksum.clone(), json!(asset.hitbox)))
+        .collect();
+
+    let mut writer = fs::File::create(cazan_build_directory.join("assets.json"))?;
+    serde_json::to_writer(&mut writer, &map)?;
+    Ok(())
+}
+
+fn preview(asset: &ParsedAsset, cazan_tmp: &Path) -> Result<(), Box<dyn Error>> {
+    let file = &asset.file;
     let image = image::open(file)?;
     let save_path = cazan_tmp.join(
         file.file_stem().unwrap().to_str().unwrap().to_owned()
             + "-"
             + &checksum(file)?[0..5]
-            + "."
-            + file.extension().unwrap().to_str().unwrap(),
+            + ".png",
     );
 
     let root = BitMapBackend::new(&save_path, image.dimensions()).into_drawing_area();
@@ -236,32 +674,170 @@ fn preview(
 
     root.draw(&elem)?;
 
-    let line_style = ShapeStyle {
-        color: RED.mix(0.6),
-        filled: true,
-        stroke_width: 2,
-    };
+    let shapes = preview_shapes(asset, image.dimensions())?;
 
-    for triangle in triangles {
-        let triangle = &vec![
-            (triangle.0.x as i32, triangle.0.y as i32),
-            (triangle.1.x as i32, triangle.1.y as i32),
-            (triangle.2.x as i32, triangle.2.y as i32),
-        ]; // TODO: Simplify this by implementing Into<Vec<(T,T)>> for Triangle in cazan common
-
-        root.draw(&Polygon::new(triangle.clone(), RED.mix(0.3)))?;
-        chart
-            .draw_series(LineSeries::new(
-                triangle
-                    .iter()
-                    .chain(std::iter::once(&triangle[0]))
-                    .map(|&(x, y)| (x as u32, y as u32)),
-                line_style,
-            ))
-            .unwrap();
+    for (shape, color) in shapes.iter().zip(PREVIEW_COLORS.iter().cycle()) {
+        let line_style = ShapeStyle {
+            color: color.mix(0.6),
+            filled: true,
+            stroke_width: 2,
+        };
+
+        for triangle in shape {
+            root.draw(&Polygon::new(triangle.clone(), color.mix(0.3)))?;
+            chart
+                .draw_series(LineSeries::new(
+                    triangle
+                        .iter()
+                        .chain(std::iter::once(&triangle[0]))
+                        .map(|&(x, y)| (x as u32, y as u32)),
+                    line_style,
+                ))
+                .unwrap();
+        }
     }
 
     root.present()?;
 
     Ok(())
 }
+
+/// The triangles of a shape, as the points of the preview, placed by `place`
+type PreviewShape = Vec<Vec<(i32, i32)>>;
+
+fn preview_shape(shape: &Shape, place: impl Fn(i32, i32) -> (i32, i32)) -> PreviewShape {
+    shape
+        .iter()
+        .map(|triangle| {
+            [triangle.0, triangle.1, triangle.2]
+                .iter()
+                .map(|point| place(point.x as i32, point.y as i32))
+                .collect()
+        })
+        .collect()
+}
+
+/// The shapes of an asset in the coordinates of the whole image
+/// The shapes of the frames of a sprite sheet are moved back to their place in the sheet
+fn preview_shapes(
+    asset: &ParsedAsset,
+    size: (u32, u32),
+) -> Result<Vec<PreviewShape>, Box<dyn Error>> {
+    let (Hitbox::Frames { frames: shapes }, Some(slicing)) =
+        (&asset.hitbox, &asset.settings.slicing)
+    else {
+        return Ok(asset
+            .hitbox
+            .shapes()
+            .map(|shape| preview_shape(shape, |x, y| (x, y)))
+            .collect());
+    };
+
+    let mut preview_shapes = vec![];
+    for (frame, shapes) in slicing.frames(&asset.file, size)?.iter().zip(shapes) {
+        let (x0, y0) = (frame.x as i32, frame.y as i32);
+        let (dx, dy) = (frame.offset.0 as i32, frame.offset.1 as i32);
+        // A rotated frame was turned by 270° before being parsed, its region is `height` pixels wide in the sheet
+        let place = |x: i32, y: i32| {
+            if frame.rotated {
+                (x0 + frame.height as i32 - 1 - (y - dy), y0 + x - dx)
+            } else {
+                (x0 + x - dx, y0 + y - dy)
+            }
+        };
+        preview_shapes.extend(shapes.iter().map(|shape| preview_shape(shape, place)));
+    }
+
+    Ok(preview_shapes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn partial_failures_have_their_own_exit_code() {
+        let parsed = || asset(false);
+        assert_eq!(exit_code(&[parsed(), parsed()], 0), ExitCode::SUCCESS);
+        assert_eq!(exit_code(&[], 0), ExitCode::SUCCESS);
+        assert_eq!(
+            exit_code(&[parsed(), parsed()], 1),
+            ExitCode::from(EXIT_PARTIAL_FAILURE)
+        );
+        assert_eq!(exit_code(&[], 3), ExitCode::FAILURE);
+    }
+
+    #[test]
+    fn cached_assets_dont_make_a_failure_partial() {
+        let cached = || asset(true);
+        assert_eq!(exit_code(&[cached(), cached()], 0), ExitCode::SUCCESS);
+        assert_eq!(exit_code(&[cached(), cached()], 1), ExitCode::FAILURE);
+        assert_eq!(
+            exit_code(&[cached(), asset(false)], 1),
+            ExitCode::from(EXIT_PARTIAL_FAILURE)
+        );
+    }
+
+    fn asset(cached: bool) -> ParsedAsset {
+        ParsedAsset {
+            file: PathBuf::from("sprite.png"),
+            checksum: String::new(),
+            settings: settings(),
+            hitbox: Hitbox::Shapes(vec![]),
+            cached,
+        }
+    }
+
+    fn settings() -> HitboxSettings {
+        HitboxSettings {
+            epsilon: DEFAULT_EPSILON,
+            alpha_threshold: 0,
+            erode: 0,
+            dilate: 0,
+            slicing: None,
+        }
+    }
+
+    #[test]
+    fn asset_files_skip_the_unsupported_files() {
+        let directory = tempfile::tempdir().unwrap();
+        for name in ["hero.png", "photo.avif", "notes.txt"] {
+            fs::write(directory.path().join(name), "").unwrap();
+        }
+        fs::create_dir(directory.path().join("tiles.png")).unwrap();
+
+        let pattern = directory.path().join("*");
+        let rules = [AssetRule {
+            pattern: Pattern::new(pattern.to_str().unwrap()).unwrap(),
+            settings: Some(settings()),
+        }];
+        let files: Vec<PathBuf> = asset_files(&rules)
+            .into_iter()
+            .map(|(file, _)| file)
+            .collect();
+        assert_eq!(files, [directory.path().join("hero.png")]);
+    }
+
+    #[test]
+    fn only_the_rebuilt_images_are_opened() {
+        let directory = tempfile::tempdir().unwrap();
+        let opaque = directory.path().join("opaque.png");
+        image::RgbImage::new(4, 4).save(&opaque).unwrap();
+        // Not an image, a cached hitbox is reused without decoding it
+        let cached = directory.path().join("cached.png");
+        fs::write(&cached, "not a png").unwrap();
+
+        let mut cache = PreBuildCache::default();
+        let key = PreBuildCache::key(&checksum(&cached).unwrap(), &settings(), None);
+        cache.insert(key, Hitbox::Shapes(vec![]));
+
+        let files = vec![(opaque, settings()), (cached.clone(), settings())];
+        let (parsed, failed) = parse_assets(files, 1, &mut cache);
+        assert!(failed.is_empty());
+        let parsed: Vec<(&PathBuf, bool)> = parsed
+            .iter()
+            .map(|asset| (&asset.file, asset.cached))
+            .collect();
+        assert_eq!(parsed, [(&cached, true)]);
+    }
+}
@@ -0,0 +1,27 @@
+//! The `schema` subcommand
+//! This command prints the JSON schema of `cazan.json`, so that editors can complete and validate it
+
+use std::process::ExitCode;
+
+use super::{GlobalOptions, SubCommandTrait};
+use crate::config;
+
+use argh::FromArgs;
+
+#[derive(PartialEq, Debug, FromArgs)]
+#[argh(
+    subcommand,
+    name = "schema",
+    description = "print the JSON schema of cazan.json"
+)]
+pub struct Schema {}
+
+impl SubCommandTrait for Schema {
+    fn run(&self, _options: &GlobalOptions) -> ExitCode {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&config::schema()).unwrap()
+        );
+        ExitCode::SUCCESS
+    }
+}
@@ -1,3 +1,5 @@
+use super::config::ConfigSubCommand;
+use super::GlobalOptions;
 use argh::FromArgs;
 use std::process::ExitCode;
 
@@ -7,18 +9,44 @@ pub enum SubCommandEnum {
     PreBuild(super::prebuild::PreBuild),
     Init(super::init::Init),
     Lock(super::lock::Lock),
+    Watch(super::watch::Watch),
+    Check(super::check::Check),
+    Schema(super::schema::Schema),
+    Config(super::config::ConfigCommand),
+    Migrate(super::migrate::Migrate),
 }
 
 pub trait SubCommandTrait {
-    fn run(&self) -> ExitCode;
+    fn run(&self, options: &GlobalOptions) -> ExitCode;
 }
 
 impl SubCommandEnum {
-    pub fn run(&self) -> ExitCode {
+    pub fn run(&self, options: &GlobalOptions) -> ExitCode {
         match self {
-            SubCommandEnum::PreBuild(prebuild) => prebuild.run(),
-            SubCommandEnum::Init(init) => init.run(),
-            SubCommandEnum::Lock(lock) => lock.run(),
+            SubCommandEnum::PreBuild(prebuild) => prebuild.run(options),
+            SubCommandEnum::Init(init) => init.run(options),
+            SubCommandEnum::Lock(lock) => lock.run(options),
+            SubCommandEnum::Watch(watch) => watch.run(options),
+            SubCommandEnum::Check(check) => check.run(options),
+            SubCommandEnum::Schema(schema) => schema.run(options),
+            SubCommandEnum::Config(config) => config.run(options),
+            SubCommandEnum::Migrate(migrate) => migrate.run(options),
+        }
+    }
+
+    /// Whether the run is logged in `.cazan/logs`, only the subcommands writing to the project are, the other ones
+    /// leave it as it is. `init` creates the `.cazan` directory
+    pub fn is_logged(&self) -> bool {
+        match self {
+            SubCommandEnum::PreBuild(_) | SubCommandEnum::Lock(_) | SubCommandEnum::Watch(_) => {
+                true
+            }
+            SubCommandEnum::Migrate(migrate) => !migrate.dry_run,
+            SubCommandEnum::Config(config) => !matches!(
+                config.subcommand,
+                ConfigSubCommand::Get(_) | ConfigSubCommand::List(_)
+            ),
+            SubCommandEnum::Init(_) | SubCommandEnum::Check(_) | SubCommandEnum::Schema(_) => false,
         }
     }
 }
@@ -0,0 +1,478 @@
+//! The `watch` subcommand
+//! This command watches the assets and the project file (`cazan.json`, `cazan.toml` or `cazan.yaml`) of your project
+//! It re-runs the prebuild pipeline for the touched assets, and re-locks the project file when it changes
+//! Only the directories the asset globs start from are watched, and the changes in `IGNORED_DIRECTORIES` are ignored
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::env;
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::mpsc;
+use std::time::Duration;
+
+use super::lock::Lock;
+use super::prebuild::{
+    asset_files, asset_rules, default_jobs, is_parsable, parse_assets, settings_for, write_assets,
+    AssetRule, ParsedAsset, SettingsOverrides,
+};
+use super::{GlobalOptions, SubCommandTrait};
+use crate::cache::PreBuildCache;
+use crate::config::Config;
+use crate::diagnostic::Diagnostic;
+use crate::hitbox::HitboxSettings;
+use crate::project::{ConfigSource, ProjectFile, FORMATS};
+
+use crate::report::reporter;
+use argh::FromArgs;
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+
+/// Time to wait for other events after a change, editors often write a file several times in a row
+const DEBOUNCE: Duration = Duration::from_millis(200);
+/// The directories of cazan, of the version control and of the builds, watched by a glob like `**/*.png`, their
+/// changes are never assets
+const IGNORED_DIRECTORIES: [&str; 4] = [".cazan", ".git", "target", "node_modules"];
+
+#[derive(PartialEq, Debug, FromArgs)]
+#[argh(
+    subcommand,
+    name = "watch",
+    description = "re-run the prebuild each time an asset or the project file changes"
+)]
+pub struct Watch {
+    #[argh(
+        option,
+        short = 'e',
+        description = "epsilon value for the Ramer-Douglas-Peucker algorithm (Image simplification)"
+    )]
+    pub epsilon: Option<f64>,
+
+    #[argh(
+        option,
+        description = "pixels with an alpha lower or equal to this value are transparent (0-255, default: 0)"
+    )]
+    pub alpha_threshold: Option<u8>,
+
+    #[argh(
+        option,
+        description = "number of pixels removed from the borders of the opaque shapes (erosion)"
+    )]
+    pub erode: Option<u32>,
+
+    #[argh(
+        option,
+        description = "number of pixels added around the opaque shapes, after the erosion (dilation)"
+    )]
+    pub dilate: Option<u32>,
+
+    #[argh(
+        option,
+        short = 'j',
+        description = "number of assets parsed in parallel (default: number of CPUs)"
+    )]
+    pub jobs: Option<usize>,
+}
+
+/// The locked config values used by the watcher
+struct WatchedConfig {
+    rules: Vec<AssetRule>,
+    jobs: usize,
+}
+
+impl WatchedConfig {
+    fn load(cazan_directory: &Path, overrides: SettingsOverrides, jobs: usize) -> Option<Self> {
+        let config = fs::read_to_string(cazan_directory.join("config.json")).ok()?;
+        let source = ConfigSource::json(".cazan/config.json", config);
+        let config = match Config::parse(&source.json) {
+            Ok((config, _)) => config,
+            Err(e) => {
+                reporter().diagnostic(&Diagnostic::from_json_error(&source, &e));
+                return None;
+            }
+        };
+        let assets = config.assets.clone().unwrap_or_default();
+
+        Some(Self {
+            rules: asset_rules(&assets, &config, overrides).ok()?,
+            jobs,
+        })
+    }
+
+    fn matches(&self, file: &Path) -> bool {
+        self.rules
+            .iter()
+            .any(|rule| rule.pattern.matches_path(file))
+    }
+
+    /// The directories to watch: the project directory for the project file, and the roots of the asset globs
+    /// The directories inside a recursively watched one are left out
+    fn watched_directories(&self) -> Vec<(PathBuf, RecursiveMode)> {
+        let mut directories: Vec<(PathBuf, RecursiveMode)> = self
+            .rules
+            .iter()
+            .map(|rule| glob_root(rule.pattern.as_str()))
+            .chain([(PathBuf::new(), RecursiveMode::NonRecursive)])
+            .collect();
+        // The recursive watch of a directory comes first
+        directories.sort_by_key(|(directory, mode)| {
+            (directory.clone(), *mode != RecursiveMode::Recursive)
+        });
+        directories.dedup_by(|(directory, _), (kept, _)| directory == kept);
+
+        let recursive: Vec<PathBuf> = directories
+            .iter()
+            .filter(|(_, mode)| *mode == RecursiveMode::Recursive)
+            .map(|(directory, _)| directory.clone())
+            .collect();
+        directories.retain(|(directory, _)| {
+            !recursive
+                .iter()
+                .any(|root| directory != root && directory.starts_with(root))
+        });
+        directories
+    }
+}
+
+/// The directory a glob starts from, with whether its subdirectories can match
+/// `assets/*.png` only needs `assets`, `assets/**/*.png` and `assets/*/idle.png` need its subdirectories too
+fn glob_root(pattern: &str) -> (PathBuf, RecursiveMode) {
+    let is_glob = |component: &Component| {
+        component
+            .as_os_str()
+            .to_str()
+            .is_some_and(|component| component.contains(['*', '?', '[']))
+    };
+    let components: Vec<Component> = Path::new(pattern)
+        .components()
+        .filter(|component| *component != Component::CurDir)
+        .collect();
+
+    match components.iter().position(is_glob) {
+        // A file, its directory is watched
+        None => {
+            let root: PathBuf = components.iter().collect();
+            let root = root.parent().map(Path::to_path_buf).unwrap_or_default();
+            (root, RecursiveMode::NonRecursive)
+        }
+        Some(first_glob) => {
+            let root = components[..first_glob].iter().collect();
+            let mode = if first_glob == components.len() - 1
+                && components[first_glob].as_os_str() != OsStr::new("**")
+            {
+                RecursiveMode::NonRecursive
+            } else {
+                RecursiveMode::Recursive
+            };
+            (root, mode)
+        }
+    }
+}
+
+/// Watch the directories of the config, instead of the `watched` ones, which are returned updated
+/// A missing directory is watched from its closest existing parent, so that its creation is seen
+fn watch_directories(
+    watcher: &mut RecommendedWatcher,
+    root: &Path,
+    config: &WatchedConfig,
+    watched: Vec<PathBuf>,
+) -> Vec<PathBuf> {
+    for directory in watched {
+        watcher.unwatch(&directory).ok();
+    }
+
+    let mut watched = vec![];
+    for (directory, mode) in config.watched_directories() {
+        let mut directory = root.join(directory);
+        let mut mode = mode;
+        while !directory.is_dir() && directory != root {
+            directory = directory.parent().unwrap_or(root).to_path_buf();
+            mode = RecursiveMode::Recursive;
+        }
+        if watched.contains(&directory) {
+            continue;
+        }
+
+        match watcher.watch(&directory, mode) {
+            Ok(()) => watched.push(directory),
+            Err(e) => {
+                let message = format!("couldn't watch `{}`: {e}", directory.display());
+                reporter().warning(&message);
+            }
+        }
+    }
+    watched
+}
+
+/// Whether a touched path is in a directory whose changes are ignored
+fn is_ignored(path: &Path) -> bool {
+    path.components().any(|component| {
+        IGNORED_DIRECTORIES
+            .iter()
+            .any(|ignored| component.as_os_str() == *ignored)
+    })
+}
+
+impl SubCommandTrait for Watch {
+    fn run(&self, options: &GlobalOptions) -> ExitCode {
+        let current_dir = env::current_dir().unwrap();
+        let cazan_directory = current_dir.join(".cazan");
+        let cazan_build_directory = cazan_directory.join("build");
+        let cache_file = cazan_build_directory.join("cache.json");
+
+        let project_file = match ProjectFile::find(&current_dir) {
+            Ok(Some(project_file)) if cazan_directory.exists() => project_file,
+            Ok(_) => {
+                reporter().error("cazan is not initialized for this directory");
+                return ExitCode::FAILURE;
+            }
+            Err(e) => {
+                reporter().error(&e);
+                return ExitCode::FAILURE;
+            }
+        };
+
+        let jobs = match self.jobs {
+            Some(0) => {
+                reporter().error("--jobs must be at least 1");
+                return ExitCode::FAILURE;
+            }
+            Some(jobs) => jobs,
+            None => default_jobs(),
+        };
+
+        let overrides = SettingsOverrides {
+            epsilon: self.epsilon,
+            alpha_threshold: self.alpha_threshold,
+            erode: self.erode,
+            dilate: self.dilate,
+        };
+
+        relock(options);
+
+        let Some(mut config) = WatchedConfig::load(&cazan_directory, overrides, jobs) else {
+            reporter().error("reading the locked config, use `cazan lock` to fix it");
+            return ExitCode::FAILURE;
+        };
+
+        let mut cache = PreBuildCache::load(&cache_file);
+        let mut assets = build_all(&config, &mut cache);
+        save(&cazan_build_directory, &cache_file, &assets, &cache);
+
+        let (sender, receiver) = mpsc::channel();
+        let mut watcher = match notify::recommended_watcher(sender) {
+            Ok(watcher) => watcher,
+            Err(e) => {
+                let message = format!("creating the file watcher: {e}");
+                reporter().error(&message);
+                return ExitCode::FAILURE;
+            }
+        };
+
+        let mut watched = watch_directories(&mut watcher, &current_dir, &config, vec![]);
+        if watched.is_empty() {
+            reporter().error("couldn't watch the project directory");
+            return ExitCode::FAILURE;
+        }
+
+        let message = format!("assets and {}, press Ctrl+C to stop", project_file.name());
+        reporter().status("Watching", &message);
+
+        while let Ok(event) = receiver.recv() {
+            let mut touched = BTreeSet::new();
+            let mut event = event;
+            loop {
+                // Reading the files (checksums, parsing) emits access events, they must be ignored
+                match event {
+                    Ok(event) if !event.kind.is_access() => touched.extend(event.paths),
+                    _ => {}
+                }
+                match receiver.recv_timeout(DEBOUNCE) {
+                    Ok(next) => event = next,
+                    Err(_) => break,
+                }
+            }
+
+            let touched: Vec<PathBuf> = touched
+                .into_iter()
+                .map(|path| {
+                    path.strip_prefix(&current_dir)
+                        .map(Path::to_path_buf)
+                        .unwrap_or(path)
+                })
+                .filter(|path| !is_ignored(path))
+                .collect();
+
+            if touched.iter().any(|path| is_project_file(path)) {
+                relock(options);
+                match WatchedConfig::load(&cazan_directory, overrides, jobs) {
+                    Some(new_config) => {
+                        config = new_config;
+                        watched = watch_directories(&mut watcher, &current_dir, &config, watched);
+                    }
+                    None => reporter()
+                        .warning("couldn't read the locked config, keeping the previous one"),
+                }
+                assets = build_all(&config, &mut cache);
+            } else {
+                let touched: Vec<PathBuf> = touched
+                    .into_iter()
+                    .filter(|path| config.matches(path))
+                    .collect();
+
+                if touched.is_empty() {
+                    continue;
+                }
+
+                for path in &touched {
+                    assets.remove(path);
+                }
+
+                let files: Vec<(PathBuf, HitboxSettings)> = touched
+                    .into_iter()
+                    .filter(|path| path.is_file())
+                    .filter_map(|path| {
+                        let settings = settings_for(&config.rules, &path)?;
+                        Some((path, settings))
+                    })
+                    .filter(|(path, _)| is_parsable(path))
+                    .collect();
+
+                let (parsed, failed) = parse_assets(files, config.jobs, &mut cache);
+                for failure in failed {
+                    failure.report();
+                }
+                for asset in parsed {
+                    assets.insert(asset.file.clone(), asset);
+                }
+            }
+
+            save(&cazan_build_directory, &cache_file, &assets, &cache);
+        }
+
+        ExitCode::SUCCESS
+    }
+}
+
+/// Whether a touched path is a project file, a new one is also relocked so that `cazan lock` reports the duplicates
+fn is_project_file(path: &Path) -> bool {
+    FORMATS
+        .iter()
+        .any(|format| path == Path::new(format.file_name()))
+}
+
+/// Re-lock the project file the way `cazan lock` does
+fn relock(options: &GlobalOptions) {
+    let lock = Lock {
+        force: false,
+        allow_unknown: false,
+    };
+
+    if lock.run(options) != ExitCode::SUCCESS {
+        reporter().warning("couldn't lock the project file, the previous locked config is used");
+    }
+}
+
+/// Build all the assets, the cache entries of the assets changed or removed since the previous full build are dropped
+fn build_all(config: &WatchedConfig, cache: &mut PreBuildCache) -> BTreeMap<PathBuf, ParsedAsset> {
+    cache.forget_usage();
+    let (parsed, failed) = parse_assets(asset_files(&config.rules), config.jobs, cache);
+    cache.prune();
+
+    for failure in failed {
+        failure.report();
+    }
+
+    parsed
+        .into_iter()
+        .map(|asset| (asset.file.clone(), asset))
+        .collect()
+}
+
+fn save(
+    cazan_build_directory: &Path,
+    cache_file: &Path,
+    assets: &BTreeMap<PathBuf, ParsedAsset>,
+    cache: &PreBuildCache,
+) {
+    if write_assets(cazan_build_directory, assets.values()).is_err() {
+        reporter().error("writing `.cazan/build/assets.json`");
+    }
+
+    if cache.save(cache_file).is_err() {
+        reporter().warning("couldn't save the prebuild cache");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use glob::Pattern;
+
+    fn watched_config(patterns: &[&str]) -> WatchedConfig {
+        WatchedConfig {
+            rules: patterns
+                .iter()
+                .map(|pattern| AssetRule {
+                    pattern: Pattern::new(pattern).unwrap(),
+                    settings: None,
+                })
+                .collect(),
+            jobs: 1,
+        }
+    }
+
+    #[test]
+    fn glob_roots() {
+        use RecursiveMode::{NonRecursive, Recursive};
+
+        assert_eq!(glob_root("*.png"), (PathBuf::new(), NonRecursive));
+        assert_eq!(
+            glob_root("./assets/*.png"),
+            (PathBuf::from("assets"), NonRecursive)
+        );
+        assert_eq!(
+            glob_root("assets/**/*.png"),
+            (PathBuf::from("assets"), Recursive)
+        );
+        assert_eq!(
+            glob_root("assets/*/idle.png"),
+            (PathBuf::from("assets"), Recursive)
+        );
+        assert_eq!(
+            glob_root("assets/chars/**"),
+            (PathBuf::from("assets/chars"), Recursive)
+        );
+        assert_eq!(
+            glob_root("assets/hero.png"),
+            (PathBuf::from("assets"), NonRecursive)
+        );
+    }
+
+    #[test]
+    fn nested_directories_are_watched_once() {
+        use RecursiveMode::{NonRecursive, Recursive};
+
+        let config = watched_config(&["assets/**/*.png", "assets/chars/*.png", "ui/*.png"]);
+        assert_eq!(
+            config.watched_directories(),
+            [
+                (PathBuf::new(), NonRecursive),
+                (PathBuf::from("assets"), Recursive),
+                (PathBuf::from("ui"), NonRecursive),
+            ]
+        );
+
+        let config = watched_config(&["**/*.png", "assets/*.png"]);
+        assert_eq!(config.watched_directories(), [(PathBuf::new(), Recursive)]);
+    }
+
+    #[test]
+    fn build_directories_are_ignored() {
+        assert!(is_ignored(Path::new("target/debug/cazan")));
+        assert!(is_ignored(Path::new(".git/index")));
+        assert!(is_ignored(Path::new("game/.cazan/logs/run.jsonl")));
+        assert!(!is_ignored(Path::new("assets/target.png")));
+    }
+}
@@ -1,30 +1,490 @@
+use indexmap::IndexMap;
+use schemars::JsonSchema;
 use semver::Version;
-use serde::{Deserialize, Serialize};
+use serde::de::{self, MapAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
 use sha2::{Digest, Sha256};
+use std::env;
+use std::fmt;
 use std::fs;
-use std::path::{Path, PathBuf};
+use std::path::PathBuf;
 
-#[derive(Serialize, Deserialize, Debug)]
+/// The config of a cazan project, `cazan.json`
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
 #[serde(rename_all = "camelCase")]
 #[allow(dead_code)]
-pub struct Config<'a> {
-    pub name: &'a str,
+pub struct Config {
+    /// Reference to the JSON schema of the config, used by the editors for completion and validation
+    #[serde(rename = "$schema", skip_serializing_if = "Option::is_none")]
+    pub schema: Option<String>,
+    /// Version of the layout of the config, the configs without it are version 1. `cazan migrate` upgrades them
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub config_version: Option<u64>,
+    /// Name of the project
+    pub name: String,
+    /// Version of the project, following the semantic versioning
     pub version: Version,
-    pub authors: Vec<&'a str>,
+    /// Authors of the project
+    pub authors: Vec<String>,
+    /// Use autoplay for the audio and video assets
     #[serde(skip_serializing_if = "Option::is_none")]
     pub use_autoplay_for_multimedia: Option<bool>,
+    /// Epsilon of the Ramer-Douglas-Peucker algorithm used to simplify the hitboxes, in pixels (default: 3)
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[schemars(extend("exclusiveMinimum" = 0))]
     pub rdp_epsilon: Option<f64>,
-    pub assets: Option<Vec<&'a str>>,
-    pub plugins: Option<Vec<PluginConfig<'a>>>,
+    /// Pixels with an alpha lower or equal to the threshold are considered transparent
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alpha_threshold: Option<u8>,
+    /// Number of pixels removed from the borders of the opaque shapes before their edges are extracted
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub erode: Option<u32>,
+    /// Number of pixels added around the opaque shapes before their edges are extracted, after the erosion
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dilate: Option<u32>,
+    /// The assets to prebuild, when several entries match a file the last one wins
+    pub assets: Option<Vec<AssetConfig>>,
+    /// The plugins of the project
+    pub plugins: Option<Vec<PluginConfig>>,
+    /// Number of logs of the runs kept in `.cazan/logs`, the oldest ones are removed (default: 20). 0 disables the logs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_logs: Option<u32>,
+    /// Named sets of settings replacing the ones above, the profile is selected with `cazan --profile <name>`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub profiles: Option<IndexMap<String, Profile>>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+/// The settings of a profile, each one given replaces the one of the base config when the profile is selected
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
 #[serde(rename_all = "camelCase")]
-pub struct PluginConfig<'a> {
-    pub name: &'a str,
+pub struct Profile {
+    /// Use autoplay for the audio and video assets
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub use_autoplay_for_multimedia: Option<bool>,
+    /// Epsilon of the Ramer-Douglas-Peucker algorithm used to simplify the hitboxes, in pixels
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schemars(extend("exclusiveMinimum" = 0))]
+    pub rdp_epsilon: Option<f64>,
+    /// Pixels with an alpha lower or equal to the threshold are considered transparent
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alpha_threshold: Option<u8>,
+    /// Number of pixels removed from the borders of the opaque shapes before their edges are extracted
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub erode: Option<u32>,
+    /// Number of pixels added around the opaque shapes before their edges are extracted, after the erosion
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dilate: Option<u32>,
+    /// The assets to prebuild, they replace the whole list of the base config
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub assets: Option<Vec<AssetConfig>>,
+    /// The plugins of the project, they replace the whole list of the base config
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub plugins: Option<Vec<PluginConfig>>,
+}
+
+/// An entry of `assets`, either a glob pattern or an object with per-asset hitbox settings
+#[derive(Serialize, JsonSchema, Debug, Clone)]
+#[serde(untagged)]
+pub enum AssetConfig {
+    /// Glob pattern of the assets (ex: `assets/sprite-*.png`)
+    Pattern(String),
+    Detailed(DetailedAssetConfig),
+}
+
+/// Assets with their own hitbox settings, they take precedence over the top-level ones
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct DetailedAssetConfig {
+    /// Glob pattern of the assets (ex: `assets/sprite-*.png`)
+    pub pattern: String,
+    /// Epsilon of the Ramer-Douglas-Peucker algorithm for these assets, in pixels
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schemars(extend("exclusiveMinimum" = 0))]
+    pub epsilon: Option<f64>,
+    /// Pixels with an alpha lower or equal to the threshold are considered transparent
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alpha_threshold: Option<u8>,
+    /// Number of pixels removed from the borders of the opaque shapes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub erode: Option<u32>,
+    /// Number of pixels added around the opaque shapes, after the erosion
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dilate: Option<u32>,
+    /// Don't prebuild these assets
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub skip: bool,
+    /// The assets are sprite sheets, each frame gets its own hitbox
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub frames: Option<FramesConfig>,
+}
+
+/// How a sprite sheet is sliced into frames, either a grid or a sidecar JSON file (Aseprite or TexturePacker)
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct FramesConfig {
+    /// Width of the frames of the grid, in pixels
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub width: Option<u32>,
+    /// Height of the frames of the grid, in pixels
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub height: Option<u32>,
+    /// Space around the grid, in pixels
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub margin: u32,
+    /// Space between the frames of the grid, in pixels
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub spacing: u32,
+    /// Number of frames of the grid, when the last row isn't full
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub count: Option<u32>,
+    /// Path of the sidecar relative to the directory of the sprite sheet, `{stem}` is replaced by the file stem of the
+    /// sprite sheet
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sidecar: Option<String>,
+}
+
+fn is_zero(value: &u32) -> bool {
+    *value == 0
+}
+
+impl AssetConfig {
+    pub fn pattern(&self) -> &str {
+        match self {
+            AssetConfig::Pattern(pattern) => pattern,
+            AssetConfig::Detailed(asset) => &asset.pattern,
+        }
+    }
+}
+
+// Not derived with `#[serde(untagged)]`, which would hide the unknown fields from `serde_ignored`
+// and replace the field errors by "data did not match any variant"
+impl<'de> Deserialize<'de> for AssetConfig {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct AssetConfigVisitor;
+
+        impl<'de> Visitor<'de> for AssetConfigVisitor {
+            type Value = AssetConfig;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a glob pattern or an asset object")
+            }
+
+            fn visit_str<E: de::Error>(self, pattern: &str) -> Result<Self::Value, E> {
+                Ok(AssetConfig::Pattern(pattern.to_string()))
+            }
+
+            fn visit_map<M: MapAccess<'de>>(self, map: M) -> Result<Self::Value, M::Error> {
+                DetailedAssetConfig::deserialize(de::value::MapAccessDeserializer::new(map))
+                    .map(AssetConfig::Detailed)
+            }
+        }
+
+        deserializer.deserialize_any(AssetConfigVisitor)
+    }
+}
+
+/// A plugin of the project
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PluginConfig {
+    /// Name of the plugin
+    pub name: String,
+    /// Version of the plugin, following the semantic versioning
     pub version: Option<Version>,
-    pub path: Option<&'a Path>,
+    /// Path of a local plugin
+    pub path: Option<PathBuf>,
+}
+
+impl Config {
+    /// Parse a config, returns it with the paths of the unknown fields (ex: `assets.0.foo`)
+    pub fn parse(source: &str) -> Result<(Self, Vec<String>), serde_json::Error> {
+        let deserializer = &mut serde_json::Deserializer::from_str(source);
+        let mut unknown: Vec<String> = vec![];
+        let config =
+            serde_ignored::deserialize(deserializer, |field| unknown.push(field.to_string()))?;
+        Ok((config, unknown))
+    }
+
+    /// Check the values that can't be checked by the types, returns the list of the problems
+    pub fn validate(&self) -> Vec<InvalidValue> {
+        let mut errors = vec![];
+
+        validate_settings("", self.rdp_epsilon, self.assets.as_deref(), &mut errors);
+        for (name, profile) in self.profiles.iter().flatten() {
+            validate_settings(
+                &format!("profiles.{name}."),
+                profile.rdp_epsilon,
+                profile.assets.as_deref(),
+                &mut errors,
+            );
+        }
+
+        errors
+    }
+}
+
+/// Check the settings of the base config or of a profile, `prefix` is the path of the profile
+fn validate_settings(
+    prefix: &str,
+    rdp_epsilon: Option<f64>,
+    assets: Option<&[AssetConfig]>,
+    errors: &mut Vec<InvalidValue>,
+) {
+    if let Some(epsilon) = rdp_epsilon {
+        if !is_valid_epsilon(epsilon) {
+            errors.push(InvalidValue::new(
+                format!("{prefix}rdpEpsilon"),
+                format!("must be a positive number (got {epsilon})"),
+            ));
+        }
+    }
+
+    for (i, asset) in assets.into_iter().flatten().enumerate() {
+        if let Err(e) = glob::Pattern::new(asset.pattern()) {
+            let path = match asset {
+                AssetConfig::Pattern(_) => format!("{prefix}assets.{i}"),
+                AssetConfig::Detailed(_) => format!("{prefix}assets.{i}.pattern"),
+            };
+            errors.push(InvalidValue::new(
+                path,
+                format!("pattern `{}` is invalid: {e}", asset.pattern()),
+            ));
+        }
+
+        let AssetConfig::Detailed(asset) = asset else {
+            continue;
+        };
+
+        if let Some(epsilon) = asset.epsilon {
+            if !is_valid_epsilon(epsilon) {
+                errors.push(InvalidValue::new(
+                    format!("{prefix}assets.{i}.epsilon"),
+                    format!("must be a positive number (got {epsilon})"),
+                ));
+            }
+        }
+
+        if let Some(frames) = &asset.frames {
+            let is_grid = frames.width.is_some() || frames.height.is_some();
+            if is_grid && frames.sidecar.is_some() {
+                errors.push(InvalidValue::new(
+                    format!("{prefix}assets.{i}.frames"),
+                    "must either be a grid or a sidecar, not both",
+                ));
+            } else if frames.sidecar.is_none()
+                && (frames.width.unwrap_or(0) == 0 || frames.height.unwrap_or(0) == 0)
+            {
+                errors.push(InvalidValue::new(
+                    format!("{prefix}assets.{i}.frames"),
+                    "needs a non-zero `width` and `height`, or a `sidecar`",
+                ));
+            }
+        }
+    }
+}
+
+/// A value of the config that is well typed but invalid
+#[derive(Debug)]
+pub struct InvalidValue {
+    /// Path of the value, with the same format as the unknown fields (ex: `assets.0.epsilon`)
+    pub path: String,
+    pub message: String,
+}
+
+impl InvalidValue {
+    fn new(path: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            message: message.into(),
+        }
+    }
+
+    /// The path as written in the messages (ex: `assets[0].epsilon`)
+    pub fn display_path(&self) -> String {
+        display_path(&self.path)
+    }
+}
+
+impl fmt::Display for InvalidValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "`{}` {}", self.display_path(), self.message)
+    }
+}
+
+/// Write a path of the config the way it is accessed in JavaScript (ex: `assets.0.epsilon` gives `assets[0].epsilon`)
+/// The `?` segments added by `serde_ignored` for the optional values are removed
+pub fn display_path(path: &str) -> String {
+    let mut display = String::new();
+    for segment in path.split('.').filter(|segment| *segment != "?") {
+        if segment.parse::<usize>().is_ok() {
+            display.push_str(&format!("[{segment}]"));
+        } else {
+            if !display.is_empty() {
+                display.push('.');
+            }
+            display.push_str(segment);
+        }
+    }
+    display
+}
+
+fn is_valid_epsilon(epsilon: f64) -> bool {
+    epsilon.is_finite() && epsilon > 0.
+}
+
+/// Type of the value of a `CAZAN_*` environment variable
+#[derive(Clone, Copy)]
+enum EnvValue {
+    String,
+    Version,
+    Bool,
+    Epsilon,
+    U8,
+    U32,
+}
+
+/// The environment variables overriding the scalar fields of the config
+const ENV_OVERRIDES: [(&str, &str, EnvValue); 7] = [
+    ("CAZAN_NAME", "name", EnvValue::String),
+    ("CAZAN_VERSION", "version", EnvValue::Version),
+    (
+        "CAZAN_USE_AUTOPLAY_FOR_MULTIMEDIA",
+        "useAutoplayForMultimedia",
+        EnvValue::Bool,
+    ),
+    ("CAZAN_RDP_EPSILON", "rdpEpsilon", EnvValue::Epsilon),
+    ("CAZAN_ALPHA_THRESHOLD", "alphaThreshold", EnvValue::U8),
+    ("CAZAN_ERODE", "erode", EnvValue::U32),
+    ("CAZAN_DILATE", "dilate", EnvValue::U32),
+];
+
+impl EnvValue {
+    fn parse(self, variable: &str, value: &str) -> Result<Value, String> {
+        // Each kind comes with what it expects, a string accepts any value
+        let (parsed, expected) = match self {
+            EnvValue::String => return Ok(Value::from(value)),
+            EnvValue::Version => (
+                Version::parse(value).ok().map(|_| Value::from(value)),
+                "a semantic version",
+            ),
+            EnvValue::Bool => (value.parse::<bool>().ok().map(Value::from), "true or false"),
+            EnvValue::Epsilon => (
+                value
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|epsilon| is_valid_epsilon(*epsilon))
+                    .map(Value::from),
+                "a positive number",
+            ),
+            EnvValue::U8 => (
+                value.parse::<u8>().ok().map(Value::from),
+                "an integer between 0 and 255",
+            ),
+            EnvValue::U32 => (
+                value.parse::<u32>().ok().map(Value::from),
+                "a non-negative integer",
+            ),
+        };
+
+        parsed.ok_or_else(|| format!("`{variable}` must be {expected} (got `{value}`)"))
+    }
+}
+
+/// What is applied to the project file when it is locked: the selected profile, then the environment overrides
+#[derive(Debug)]
+pub struct ConfigOverlay {
+    pub profile: Option<String>,
+    /// The fields overridden by the `CAZAN_*` environment variables, with their variable
+    env: Vec<(&'static str, &'static str, Value)>,
+}
+
+impl ConfigOverlay {
+    /// The overlay of a profile, with the overrides of the environment variables
+    pub fn new(profile: Option<String>) -> Result<Self, String> {
+        let mut env = vec![];
+        for (variable, field, kind) in ENV_OVERRIDES {
+            if let Ok(value) = env::var(variable) {
+                env.push((variable, field, kind.parse(variable, &value)?));
+            }
+        }
+        Ok(Self { profile, env })
+    }
+
+    /// Apply the overlay to a JSON config, the `profiles` section is removed from the resolved config
+    pub fn apply(&self, json: &str) -> Result<String, String> {
+        let mut config: Value = serde_json::from_str(json).map_err(|e| e.to_string())?;
+        let Some(fields) = config.as_object_mut() else {
+            return Err("the config must be an object".to_string());
+        };
+
+        let profiles = fields.shift_remove("profiles");
+        if let Some(name) = &self.profile {
+            let profile = profiles
+                .as_ref()
+                .and_then(|profiles| profiles.get(name))
+                .and_then(Value::as_object);
+            let Some(profile) = profile else {
+                let names: Vec<&str> = profiles
+                    .as_ref()
+                    .and_then(Value::as_object)
+                    .map(|profiles| profiles.keys().map(String::as_str).collect())
+                    .unwrap_or_default();
+                return Err(if names.is_empty() {
+                    format!("unknown profile `{name}`, the config has no profiles")
+                } else {
+                    format!(
+                        "unknown profile `{name}`, the profiles of the config are: {}",
+                        names.join(", ")
+                    )
+                });
+            };
+            for (field, value) in profile {
+                fields.insert(field.clone(), value.clone());
+            }
+        }
+
+        for (_, field, value) in &self.env {
+            fields.insert(field.to_string(), value.clone());
+        }
+
+        Ok(serde_json::to_string_pretty(&config).unwrap())
+    }
+
+    /// The checksum written in `.cazan/checksum.txt`: the checksum of the project file, followed on a second line by
+    /// the one of the profile and the overrides when there are some, so that changing them requires a new lock
+    pub fn lock_checksum(&self, file_checksum: String) -> String {
+        match self.selection_checksum() {
+            Some(selection) => format!("{file_checksum}\n{selection}"),
+            None => file_checksum,
+        }
+    }
+
+    /// Whether the locked config was resolved with this profile and these overrides, `locked` is the content of
+    /// `.cazan/checksum.txt`
+    pub fn is_locked(&self, locked: &str) -> bool {
+        locked.lines().nth(1).map(str::to_string) == self.selection_checksum()
+    }
+
+    fn selection_checksum(&self) -> Option<String> {
+        if self.profile.is_none() && self.env.is_empty() {
+            return None;
+        }
+
+        let mut sha256 = Sha256::new();
+        if let Some(profile) = &self.profile {
+            sha256.update(format!("profile={profile}\n"));
+        }
+        for (variable, _, value) in &self.env {
+            sha256.update(format!("{variable}={value}\n"));
+        }
+        Some(format!("{:x}", sha256.finalize()))
+    }
+}
+
+/// The JSON schema of `cazan.json`
+pub fn schema() -> serde_json::Value {
+    serde_json::to_value(schemars::schema_for!(Config)).unwrap()
 }
 
 pub fn checksum(file: &PathBuf) -> Result<String, std::io::Error> {
@@ -33,3 +493,166 @@ pub fn checksum(file: &PathBuf) -> Result<String, std::io::Error> {
     std::io::copy(&mut file, &mut sha256)?;
     Ok(format!("{:x}", sha256.finalize()))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_escaped_strings() {
+        let source = r#"{
+            "name": "my \"game\"",
+            "version": "1.0.0",
+            "authors": ["C:\\games"],
+            "assets": ["assets\\*.png", { "pattern": "sheets/\u0041*.png" }],
+            "plugins": null
+        }"#;
+        let (config, unknown) = Config::parse(source).unwrap();
+
+        assert!(unknown.is_empty());
+        assert_eq!(config.name, r#"my "game""#);
+        assert_eq!(config.authors, [r"C:\games"]);
+        let patterns: Vec<&str> = config
+            .assets
+            .iter()
+            .flatten()
+            .map(AssetConfig::pattern)
+            .collect();
+        assert_eq!(patterns, [r"assets\*.png", "sheets/A*.png"]);
+    }
+
+    #[test]
+    fn parse_reports_unknown_fields() {
+        let source = r#"{
+            "name": "game",
+            "version": "1.0.0",
+            "authors": [],
+            "assets": [{ "pattern": "*.png", "epsilonn": 2 }],
+            "plugins": null,
+            "foo": 1
+        }"#;
+        let (_, unknown) = Config::parse(source).unwrap();
+        assert_eq!(unknown, ["assets.?.0.epsilonn", "foo"]);
+    }
+
+    #[test]
+    fn validate_epsilons_and_patterns() {
+        let source = r#"{
+            "name": "game",
+            "version": "1.0.0",
+            "authors": [],
+            "rdpEpsilon": 0,
+            "assets": ["[", { "pattern": "*.png", "epsilon": -1 }],
+            "plugins": null,
+            "profiles": { "release": { "rdpEpsilon": -2 } }
+        }"#;
+        let (config, _) = Config::parse(source).unwrap();
+        let paths: Vec<String> = config.validate().into_iter().map(|e| e.path).collect();
+        assert_eq!(
+            paths,
+            [
+                "rdpEpsilon",
+                "assets.0",
+                "assets.1.epsilon",
+                "profiles.release.rdpEpsilon"
+            ]
+        );
+    }
+
+    const PROFILES: &str = r#"{
+        "name": "game",
+        "version": "1.0.0",
+        "authors": [],
+        "rdpEpsilon": 3,
+        "assets": ["*.png"],
+        "plugins": null,
+        "profiles": { "release": { "rdpEpsilon": 1.5, "assets": ["release/*.png"] } }
+    }"#;
+
+    fn overlay(
+        profile: Option<&str>,
+        env: Vec<(&'static str, &'static str, Value)>,
+    ) -> ConfigOverlay {
+        ConfigOverlay {
+            profile: profile.map(str::to_string),
+            env,
+        }
+    }
+
+    #[test]
+    fn overlay_applies_the_profile_then_the_environment() {
+        let overlay = overlay(
+            Some("release"),
+            vec![("CAZAN_RDP_EPSILON", "rdpEpsilon", Value::from(0.5))],
+        );
+        let resolved = overlay.apply(PROFILES).unwrap();
+        let (config, _) = Config::parse(&resolved).unwrap();
+
+        assert_eq!(config.rdp_epsilon, Some(0.5));
+        assert_eq!(config.assets.unwrap()[0].pattern(), "release/*.png");
+        assert!(config.profiles.is_none());
+    }
+
+    #[test]
+    fn overlay_with_escaped_values() {
+        let overlay = overlay(
+            None,
+            vec![("CAZAN_NAME", "name", Value::from(r#"C:\games "x""#))],
+        );
+        let resolved = overlay.apply(PROFILES).unwrap();
+        let (config, _) = Config::parse(&resolved).unwrap();
+        assert_eq!(config.name, r#"C:\games "x""#);
+    }
+
+    #[test]
+    fn overlay_with_unknown_profile() {
+        let error = overlay(Some("debug"), vec![]).apply(PROFILES).unwrap_err();
+        assert_eq!(
+            error,
+            "unknown profile `debug`, the profiles of the config are: release"
+        );
+    }
+
+    #[test]
+    fn lock_checksum_changes_with_the_overlay() {
+        let checksum = "abc".to_string();
+        let none = overlay(None, vec![]);
+        let release = overlay(Some("release"), vec![]);
+        let env = overlay(None, vec![("CAZAN_ERODE", "erode", Value::from(1))]);
+
+        assert_eq!(none.lock_checksum(checksum.clone()), checksum);
+        let locked_release = release.lock_checksum(checksum.clone());
+        let locked_env = env.lock_checksum(checksum.clone());
+        assert!(locked_release.starts_with("abc\n"));
+        assert_ne!(locked_release, locked_env);
+
+        assert!(none.is_locked(&checksum));
+        assert!(release.is_locked(&locked_release));
+        assert!(!release.is_locked(&checksum));
+        assert!(!release.is_locked(&locked_env));
+        assert!(!none.is_locked(&locked_release));
+    }
+
+    #[test]
+    fn environment_values_are_checked() {
+        assert_eq!(
+            EnvValue::U8.parse("CAZAN_ALPHA_THRESHOLD", "8"),
+            Ok(Value::from(8))
+        );
+        assert_eq!(
+            EnvValue::Epsilon.parse("CAZAN_RDP_EPSILON", "-1"),
+            Err("`CAZAN_RDP_EPSILON` must be a positive number (got `-1`)".to_string())
+        );
+        assert!(EnvValue::Version.parse("CAZAN_VERSION", "1.0").is_err());
+        assert_eq!(
+            EnvValue::String.parse("CAZAN_NAME", ""),
+            Ok(Value::from(""))
+        );
+    }
+
+    #[test]
+    fn display_paths() {
+        assert_eq!(display_path("assets.?.0.epsilon"), "assets[0].epsilon");
+        assert_eq!(display_path("name"), "name");
+    }
+}
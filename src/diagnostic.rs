@@ -0,0 +1,695 @@
+//! Diagnostics of the config files, rendered like the ones of rustc
+//! They show the file, line and column of the problem, the offending line with a caret under it and a hint
+//!
+//! ```text
+//! error: unknown field `rdpEpsillon`
+//!  --> cazan.json:6:3
+//!   |
+//! 6 |   "rdpEpsillon": 2,
+//!   |   ^^^^^^^^^^^^^ unknown field
+//!   |
+//!   = help: did you mean `rdpEpsilon`?
+//! ```
+
+use std::fmt;
+
+use crate::config::{self, InvalidValue};
+use crate::project::ConfigSource;
+
+use colored::Colorize;
+use serde::Serialize;
+use serde_json::Value;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Level {
+    Error,
+    Warning,
+}
+
+/// The location of a diagnostic in its file
+#[derive(Debug, Serialize)]
+struct Snippet {
+    line: usize,
+    /// Column of the first character of the span, starting at 1
+    column: usize,
+    #[serde(skip)]
+    text: String,
+    /// Number of characters under the caret
+    length: usize,
+    label: String,
+}
+
+/// Serialized for `--message-format json`, without the level and the text of the snippet
+#[derive(Debug, Serialize)]
+pub struct Diagnostic {
+    #[serde(skip)]
+    level: Level,
+    message: String,
+    file: String,
+    #[serde(flatten)]
+    snippet: Option<Snippet>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    help: Option<String>,
+}
+
+impl Diagnostic {
+    /// A syntax or type error of the config
+    /// The errors of the configs that aren't the text of their file (TOML, YAML, migrated configs) are located by
+    /// their path, in the text of the JSON file when there is one
+    pub fn from_json_error(source: &ConfigSource, error: &serde_json::Error) -> Self {
+        let message = error.to_string();
+        // The location is shown in the snippet
+        let mut message = match message.rfind(" at line ") {
+            Some(i) => message[..i].to_string(),
+            None => message,
+        };
+        let label = match message.find(", expected ") {
+            Some(i) => message[i + 2..].to_string(),
+            None => match error.classify() {
+                serde_json::error::Category::Syntax => "invalid JSON".to_string(),
+                serde_json::error::Category::Eof => "unexpected end of file".to_string(),
+                _ => "invalid value".to_string(),
+            },
+        };
+
+        let json = &source.json;
+        let text = json
+            .lines()
+            .nth(error.line().max(1) - 1)
+            .unwrap_or_default();
+        // For the type errors serde_json gives the column of the end of the value, the caret is put under its start
+        let column = match error.classify() {
+            serde_json::error::Category::Data => token_start(text, error.column()),
+            _ => error.column().max(1),
+        };
+
+        let is_file_text = source.text.as_ref() == Some(json);
+        let snippet = if is_file_text && error.line() > 0 {
+            Some(Snippet {
+                line: error.line(),
+                column,
+                text: text.to_string(),
+                length: 1,
+                label,
+            })
+        } else {
+            let line_start: usize = json
+                .lines()
+                .take(error.line().max(1) - 1)
+                .map(|line| line.len() + 1)
+                .sum();
+            let offset = line_start
+                + text
+                    .chars()
+                    .take(column - 1)
+                    .map(char::len_utf8)
+                    .sum::<usize>();
+            let path = path_at(json, offset);
+            let path: Vec<&str> = path.iter().map(String::as_str).collect();
+            let snippet = match &source.text {
+                Some(text) if !path.is_empty() => locate_value(text, &path)
+                    .and_then(|(start, end)| span_snippet(text, start, end, &label)),
+                _ => None,
+            };
+            if snippet.is_none() && !path.is_empty() {
+                message.push_str(&format!(" at `{}`", config::display_path(&path.join("."))));
+            }
+            snippet
+        };
+
+        Self {
+            level: Level::Error,
+            message,
+            file: source.file.clone(),
+            snippet,
+            help: None,
+        }
+    }
+
+    /// A syntax error of a TOML or YAML file, `span` is the byte range of the error in `text`
+    pub fn syntax(file: &str, text: &str, span: Option<(usize, usize)>, message: &str) -> Self {
+        Self {
+            level: Level::Error,
+            message: message.trim().to_string(),
+            file: file.to_string(),
+            snippet: span.and_then(|(start, end)| span_snippet(text, start, end, "invalid syntax")),
+            help: None,
+        }
+    }
+
+    /// An unknown field found by `serde_ignored`, with a suggestion when it is close to a known field
+    pub fn unknown_field(level: Level, source: &ConfigSource, path: &str) -> Self {
+        let segments: Vec<&str> = path.split('.').filter(|segment| *segment != "?").collect();
+        let field = segments.last().copied().unwrap_or_default();
+
+        let help = known_fields(&segments[..segments.len().saturating_sub(1)])
+            .into_iter()
+            .map(|known| (distance(field, &known), known))
+            .filter(|(distance, _)| *distance <= (field.chars().count() / 3).max(1))
+            .min_by_key(|(distance, _)| *distance)
+            .map(|(_, known)| format!("did you mean `{known}`?"));
+
+        Self {
+            level,
+            message: format!("unknown field `{}`", config::display_path(path)),
+            file: source.file.clone(),
+            snippet: snippet(source, &segments, "unknown field"),
+            help,
+        }
+    }
+
+    /// A value rejected by `Config::validate`
+    pub fn invalid_value(source: &ConfigSource, error: &InvalidValue) -> Self {
+        let segments: Vec<&str> = error.path.split('.').collect();
+
+        Self {
+            level: Level::Error,
+            message: error.to_string(),
+            file: source.file.clone(),
+            // The message is in the header, the snippet only points at the value
+            snippet: snippet(source, &segments, "invalid value"),
+            help: None,
+        }
+    }
+
+    pub fn level(&self) -> Level {
+        self.level
+    }
+
+    /// Print the diagnostic on stderr, followed by an empty line like rustc
+    pub fn eprint(&self) {
+        eprintln!("{self}\n");
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let level = match self.level {
+            Level::Error => "error".bright_red().bold(),
+            Level::Warning => "warning".bright_yellow().bold(),
+        };
+        writeln!(
+            f,
+            "{}{} {}",
+            level,
+            ":".bold(),
+            self.message.as_str().bold()
+        )?;
+
+        let Some(snippet) = &self.snippet else {
+            write!(f, "{} {}", "-->".bright_blue().bold(), self.file)?;
+            if let Some(help) = &self.help {
+                write!(f, "\n{} help: {help}", "=".bright_blue().bold())?;
+            }
+            return Ok(());
+        };
+
+        let width = snippet.line.to_string().len();
+        let gutter = format!("{:width$} |", "").bright_blue().bold();
+        let caret = "^".repeat(snippet.length.max(1));
+        let caret = match self.level {
+            Level::Error => format!("{caret} {}", snippet.label).bright_red().bold(),
+            Level::Warning => format!("{caret} {}", snippet.label).bright_yellow().bold(),
+        };
+
+        writeln!(
+            f,
+            "{}{} {}:{}:{}",
+            " ".repeat(width),
+            "-->".bright_blue().bold(),
+            self.file,
+            snippet.line,
+            snippet.column
+        )?;
+        writeln!(f, "{gutter}")?;
+        writeln!(
+            f,
+            "{} {}",
+            format!("{} |", snippet.line).bright_blue().bold(),
+            snippet.text
+        )?;
+        write!(
+            f,
+            "{gutter} {}{caret}",
+            " ".repeat(snippet.column.saturating_sub(1))
+        )?;
+        if let Some(help) = &self.help {
+            write!(
+                f,
+                "\n{gutter}\n{}{} help: {help}",
+                " ".repeat(width + 1),
+                "=".bright_blue().bold()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// The snippet of the value at `path`, the key is underlined for the members of the objects
+/// The TOML and YAML files have no snippet, their diagnostics give the path of the value, and neither have the values
+/// a migration moved
+fn snippet(source: &ConfigSource, path: &[&str], label: &str) -> Option<Snippet> {
+    let text = source.text.as_ref()?;
+    let (start, end) = locate(text, path)?;
+    span_snippet(text, start, end, label)
+}
+
+fn span_snippet(text: &str, start: usize, end: usize, label: &str) -> Option<Snippet> {
+    let start = start.min(text.len());
+    let line_start = text[..start].rfind('\n').map_or(0, |i| i + 1);
+    let line_end = text[start..].find('\n').map_or(text.len(), |i| start + i);
+
+    Some(Snippet {
+        line: text[..start].matches('\n').count() + 1,
+        column: text[line_start..start].chars().count() + 1,
+        text: text[line_start..line_end]
+            .trim_end_matches('\r')
+            .to_string(),
+        length: text[start..end.clamp(start, line_end)].chars().count(),
+        label: label.to_string(),
+    })
+}
+
+/// Column of the start of the token ending at `column`, for the errors reported at the end of a token
+fn token_start(line: &str, column: usize) -> usize {
+    let chars: Vec<char> = line.chars().collect();
+    let end = column.min(chars.len());
+    if end == 0 {
+        return column.max(1);
+    }
+
+    let mut start = end - 1;
+    if chars[start] == '"' {
+        // A string, the caret goes under its opening quote
+        while start > 0 && !(chars[start - 1] == '"' && (start < 2 || chars[start - 2] != '\\')) {
+            start -= 1;
+        }
+        return start.max(1);
+    }
+    while start > 0 && (chars[start - 1].is_alphanumeric() || "-+.".contains(chars[start - 1])) {
+        start -= 1;
+    }
+    start + 1
+}
+
+/// The fields accepted by the object at `path` of the config, read from its JSON schema
+fn known_fields(path: &[&str]) -> Vec<String> {
+    let schema = config::schema();
+    let resolve = |node: &Value| -> Value {
+        match node.get("$ref").and_then(Value::as_str) {
+            Some(reference) => schema
+                .pointer(reference.trim_start_matches('#'))
+                .cloned()
+                .unwrap_or(Value::Null),
+            None => node.clone(),
+        }
+    };
+
+    // The objects among the alternatives of a node (`anyOf` of the assets)
+    let objects = |node: &Value| -> Vec<Value> {
+        let node = resolve(node);
+        let mut objects = vec![node.clone()];
+        for alternatives in ["anyOf", "oneOf"] {
+            if let Some(Value::Array(alternatives)) = node.get(alternatives) {
+                objects.extend(alternatives.iter().map(&resolve));
+            }
+        }
+        objects
+    };
+
+    let mut nodes = vec![schema.clone()];
+    for segment in path {
+        nodes = nodes
+            .iter()
+            .flat_map(&objects)
+            .filter_map(|node| match segment.parse::<usize>() {
+                Ok(_) => node.get("items").cloned(),
+                // The keys of the maps (`profiles`) are not known, all of them have the same schema
+                Err(_) => node
+                    .get("properties")
+                    .and_then(|properties| properties.get(*segment))
+                    .or_else(|| {
+                        node.get("additionalProperties")
+                            .filter(|node| node.is_object())
+                    })
+                    .cloned(),
+            })
+            .collect();
+    }
+
+    nodes
+        .iter()
+        .flat_map(objects)
+        .filter_map(|node| node.get("properties")?.as_object().cloned())
+        .flat_map(|properties| properties.keys().cloned().collect::<Vec<_>>())
+        .collect()
+}
+
+/// Levenshtein distance between two strings
+fn distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, a) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a != *b);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+/// Byte range of the value at `path` in a JSON document, or of its key for the members of the objects
+fn locate(source: &str, path: &[&str]) -> Option<(usize, usize)> {
+    let mut scanner = Scanner {
+        bytes: source.as_bytes(),
+        position: 0,
+    };
+    scanner.whitespace();
+    scanner.find(path)
+}
+
+/// Byte range of the value at `path` in a JSON document, the members of the objects included
+fn locate_value(source: &str, path: &[&str]) -> Option<(usize, usize)> {
+    let (start, end) = locate(source, path)?;
+    if path.last()?.parse::<usize>().is_ok() {
+        return Some((start, end));
+    }
+
+    let mut scanner = Scanner {
+        bytes: source.as_bytes(),
+        position: end,
+    };
+    scanner.eat(b':')?;
+    scanner.whitespace();
+    let start = scanner.position;
+    scanner.skip_value()?;
+    Some((start, scanner.position))
+}
+
+/// Path of the deepest value containing the byte `offset` of a JSON document
+fn path_at(source: &str, offset: usize) -> Vec<String> {
+    let mut scanner = Scanner {
+        bytes: source.as_bytes(),
+        position: 0,
+    };
+    let mut deepest = vec![];
+    scanner.whitespace();
+    scanner.walk(&mut vec![], offset, &mut deepest);
+    deepest
+}
+
+struct Scanner<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Scanner<'a> {
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.position).copied()
+    }
+
+    fn whitespace(&mut self) {
+        while self.peek().is_some_and(|byte| byte.is_ascii_whitespace()) {
+            self.position += 1;
+        }
+    }
+
+    fn eat(&mut self, byte: u8) -> Option<()> {
+        self.whitespace();
+        (self.peek()? == byte).then(|| self.position += 1)
+    }
+
+    /// Find `path` in the value starting at the current position
+    fn find(&mut self, path: &[&str]) -> Option<(usize, usize)> {
+        let Some((segment, rest)) = path.split_first() else {
+            let start = self.position;
+            self.skip_value()?;
+            return Some((start, self.position));
+        };
+
+        match self.peek()? {
+            b'{' => {
+                self.position += 1;
+                loop {
+                    self.whitespace();
+                    if self.peek()? == b'}' {
+                        return None;
+                    }
+                    let key_start = self.position;
+                    let key = self.string()?;
+                    let key_end = self.position;
+                    self.eat(b':')?;
+                    self.whitespace();
+                    if key == *segment {
+                        if rest.is_empty() {
+                            return Some((key_start, key_end));
+                        }
+                        return self.find(rest);
+                    }
+                    self.skip_value()?;
+                    self.whitespace();
+                    if self.peek()? == b',' {
+                        self.position += 1;
+                    }
+                }
+            }
+            b'[' => {
+                let index: usize = segment.parse().ok()?;
+                self.position += 1;
+                for i in 0.. {
+                    self.whitespace();
+                    if self.peek()? == b']' {
+                        return None;
+                    }
+                    if i == index {
+                        return self.find(rest);
+                    }
+                    self.skip_value()?;
+                    self.whitespace();
+                    if self.peek()? == b',' {
+                        self.position += 1;
+                    }
+                }
+                None
+            }
+            _ => None,
+        }
+    }
+
+    /// Walk the value starting at the current position, `deepest` is the path of the deepest value containing `offset`
+    fn walk(
+        &mut self,
+        path: &mut Vec<String>,
+        offset: usize,
+        deepest: &mut Vec<String>,
+    ) -> Option<()> {
+        let start = self.position;
+
+        match self.peek()? {
+            b'{' => {
+                self.position += 1;
+                loop {
+                    self.whitespace();
+                    if self.peek()? == b'}' {
+                        self.position += 1;
+                        break;
+                    }
+                    path.push(self.string()?.to_string());
+                    self.eat(b':')?;
+                    self.whitespace();
+                    self.walk(path, offset, deepest)?;
+                    path.pop();
+                    self.whitespace();
+                    if self.peek()? == b',' {
+                        self.position += 1;
+                    }
+                }
+            }
+            b'[' => {
+                self.position += 1;
+                for i in 0.. {
+                    self.whitespace();
+                    if self.peek()? == b']' {
+                        self.position += 1;
+                        break;
+                    }
+                    path.push(i.to_string());
+                    self.walk(path, offset, deepest)?;
+                    path.pop();
+                    self.whitespace();
+                    if self.peek()? == b',' {
+                        self.position += 1;
+                    }
+                }
+            }
+            _ => self.skip_value()?,
+        }
+
+        if (start..=self.position).contains(&offset) && path.len() >= deepest.len() {
+            *deepest = path.clone();
+        }
+        Some(())
+    }
+
+    /// Read a string, its escape sequences are kept as is
+    fn string(&mut self) -> Option<&'a str> {
+        if self.peek()? != b'"' {
+            return None;
+        }
+        let start = self.position + 1;
+        self.position += 1;
+        loop {
+            match self.peek()? {
+                b'\\' => self.position += 2,
+                b'"' => break,
+                _ => self.position += 1,
+            }
+        }
+        self.position += 1;
+        std::str::from_utf8(&self.bytes[start..self.position - 1]).ok()
+    }
+
+    fn skip_value(&mut self) -> Option<()> {
+        match self.peek()? {
+            b'"' => {
+                self.string()?;
+            }
+            b'{' | b'[' => {
+                let mut depth = 0;
+                loop {
+                    match self.peek()? {
+                        b'"' => {
+                            self.string()?;
+                            continue;
+                        }
+                        b'{' | b'[' => depth += 1,
+                        b'}' | b']' => depth -= 1,
+                        _ => {}
+                    }
+                    self.position += 1;
+                    if depth == 0 {
+                        break;
+                    }
+                }
+            }
+            _ => {
+                while self
+                    .peek()
+                    .is_some_and(|byte| !b",}] \t\r\n".contains(&byte))
+                {
+                    self.position += 1;
+                }
+            }
+        }
+        Some(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::migration;
+
+    const SOURCE: &str = r#"{
+  "name": "game",
+  "version": "1.0.0",
+  "authors": [],
+  "assets": [{ "pattern": "*.png", "epsilon": -1, "alphaTreshold": 8 }],
+  "plugins": null
+}"#;
+
+    fn source() -> ConfigSource {
+        ConfigSource::json("cazan.json", SOURCE.to_string())
+    }
+
+    #[test]
+    fn levenshtein_distance() {
+        assert_eq!(distance("kitten", "sitting"), 3);
+        assert_eq!(distance("", "abc"), 3);
+        assert_eq!(distance("same", "same"), 0);
+    }
+
+    #[test]
+    fn unknown_field_suggests_the_closest_field() {
+        let diagnostic =
+            Diagnostic::unknown_field(Level::Warning, &source(), "assets.?.0.alphaTreshold");
+
+        assert_eq!(
+            diagnostic.message,
+            "unknown field `assets[0].alphaTreshold`"
+        );
+        assert_eq!(
+            diagnostic.help.as_deref(),
+            Some("did you mean `alphaThreshold`?")
+        );
+        let snippet = diagnostic.snippet.unwrap();
+        assert_eq!((snippet.line, snippet.column), (5, 51));
+    }
+
+    #[test]
+    fn unknown_field_without_close_field() {
+        let diagnostic = Diagnostic::unknown_field(Level::Warning, &source(), "foo");
+        assert_eq!(diagnostic.help, None);
+    }
+
+    #[test]
+    fn invalid_value_prints_its_message_once() {
+        let (config, _) = Config::parse(SOURCE).unwrap();
+        let error = &config.validate()[0];
+        let diagnostic = Diagnostic::invalid_value(&source(), error);
+
+        assert_eq!(
+            diagnostic.message,
+            "`assets[0].epsilon` must be a positive number (got -1)"
+        );
+        assert_eq!(diagnostic.snippet.unwrap().label, "invalid value");
+    }
+
+    #[test]
+    fn type_error_points_at_the_value() {
+        let source = ConfigSource::json("cazan.json", SOURCE.replace("\"game\"", "12"));
+        let error = Config::parse(&source.json).unwrap_err();
+        let diagnostic = Diagnostic::from_json_error(&source, &error);
+
+        assert_eq!(
+            diagnostic.message,
+            "invalid type: integer `12`, expected a string"
+        );
+        let snippet = diagnostic.snippet.unwrap();
+        assert_eq!((snippet.line, snippet.column), (2, 11));
+        assert_eq!(snippet.label, "expected a string");
+    }
+
+    #[test]
+    fn migrated_configs_keep_the_snippets_of_their_file() {
+        // Without `configVersion`, the config is migrated in memory
+        let source = ConfigSource::json("cazan.json", SOURCE.replace("\"game\"", "12"));
+        let (source, version) = migration::upgrade(source).unwrap();
+        assert_eq!(version, Some(1));
+
+        let error = Config::parse(&source.json).unwrap_err();
+        let diagnostic = Diagnostic::from_json_error(&source, &error);
+        assert_eq!(
+            diagnostic.message,
+            "invalid type: integer `12`, expected a string"
+        );
+        let snippet = diagnostic.snippet.unwrap();
+        assert_eq!((snippet.line, snippet.column), (2, 11));
+
+        let diagnostic =
+            Diagnostic::unknown_field(Level::Warning, &source, "assets.?.0.alphaTreshold");
+        let snippet = diagnostic.snippet.unwrap();
+        assert_eq!((snippet.line, snippet.column), (5, 51));
+    }
+
+    #[test]
+    fn path_of_an_offset() {
+        let offset = SOURCE.find("-1").unwrap();
+        assert_eq!(path_at(SOURCE, offset), ["assets", "0", "epsilon"]);
+    }
+}
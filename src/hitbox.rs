@@ -0,0 +1,424 @@
+//! Computation of the hitbox of a single asset
+//! The image is decoded, the outlines of its opaque components are extracted, simplified with the Ramer-Douglas-Peucker
+//! algorithm and triangulated around their holes
+
+use std::fmt;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::outline::outlines;
+use crate::sheet::Slicing;
+
+use cazan_common::geometry::{Point, Polygon, Triangle};
+use cazan_common::image::Grid;
+use cazan_common::rdp::rdp;
+use earcutr::earcut;
+use image::io::Reader as ImageReader;
+use image::{imageops, ImageDecoder, ImageFormat, RgbaImage};
+use serde::{Deserialize, Serialize};
+
+/// Why the hitbox of an asset couldn't be computed
+#[derive(Debug)]
+pub enum HitboxError {
+    Io(std::io::Error),
+    Decode(image::ImageError),
+    /// The image has no opaque component big enough to make a polygon
+    EmptyPolygon,
+    Triangulation(earcutr::Error),
+    /// The sprite sheet couldn't be sliced into frames
+    Frames(String),
+}
+
+impl fmt::Display for HitboxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HitboxError::Io(e) => write!(f, "couldn't read the file: {e}"),
+            HitboxError::Decode(e) => write!(f, "couldn't decode the image: {e}"),
+            HitboxError::EmptyPolygon => write!(f, "the image has no opaque shape"),
+            HitboxError::Triangulation(e) => write!(f, "couldn't triangulate the polygon: {e}"),
+            HitboxError::Frames(e) => write!(f, "couldn't slice the sprite sheet: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for HitboxError {}
+
+impl From<std::io::Error> for HitboxError {
+    fn from(e: std::io::Error) -> Self {
+        HitboxError::Io(e)
+    }
+}
+
+impl From<image::ImageError> for HitboxError {
+    fn from(e: image::ImageError) -> Self {
+        HitboxError::Decode(e)
+    }
+}
+
+impl From<earcutr::Error> for HitboxError {
+    fn from(e: earcutr::Error) -> Self {
+        HitboxError::Triangulation(e)
+    }
+}
+
+/// The parameters used to compute the hitbox of an asset
+#[derive(Clone, PartialEq, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HitboxSettings {
+    pub epsilon: f64,
+    /// Pixels with an alpha lower or equal to the threshold are considered transparent
+    pub alpha_threshold: u8,
+    /// Number of erosion passes, each one removes a pixel from the borders of the opaque shapes
+    pub erode: u32,
+    /// Number of dilation passes, each one adds a pixel around the opaque shapes, applied after the erosion
+    pub dilate: u32,
+    /// The asset is a sprite sheet sliced into frames
+    pub slicing: Option<Slicing>,
+}
+
+/// Time spent in each stage of the computation of a hitbox, and what the simplification saved, shown with `-v`
+#[derive(Default, Clone, Copy, Debug)]
+pub struct Stats {
+    pub decode: Duration,
+    /// Opacity threshold, erosion, dilation and tracing of the outlines
+    pub edges: Duration,
+    pub rdp: Duration,
+    pub triangulation: Duration,
+    /// Number of vertices of the outlines before the simplification
+    pub vertices_before: usize,
+    /// Number of vertices of the outlines after the simplification, the components too small are not counted
+    pub vertices_after: usize,
+}
+
+/// Why the file can't be parsed, `None` when it is an image whose format is supported, the decoders are selected
+/// with the cargo features
+pub fn unsupported_reason(file: &Path) -> Option<String> {
+    match ImageFormat::from_path(file) {
+        Ok(format) if format.reading_enabled() => None,
+        Ok(format) => Some(format!(
+            "the {format:?} format isn't supported by this build of cazan"
+        )),
+        Err(_) => Some("it isn't an image".to_string()),
+    }
+}
+
+/// Whether the image has an alpha channel, only its header is read
+pub fn has_alpha_channel(file: &Path) -> Result<bool, HitboxError> {
+    let decoder = ImageReader::open(file)?
+        .with_guessed_format()?
+        .into_decoder()?;
+    Ok(decoder.color_type().has_alpha())
+}
+
+/// The triangles of a connected opaque component of an asset
+pub type Shape = Vec<Triangle>;
+
+/// The hitbox of an asset, written as is in `.cazan/build/assets.json`
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(untagged)]
+pub enum Hitbox {
+    /// One shape per connected opaque component
+    Shapes(Vec<Shape>),
+    /// The shapes of each frame of a sprite sheet, relative to the frame
+    Frames { frames: Vec<Vec<Shape>> },
+}
+
+impl Hitbox {
+    /// The shapes of the asset, those of all the frames for a sprite sheet
+    pub fn shapes(&self) -> Box<dyn Iterator<Item = &Shape> + '_> {
+        match self {
+            Hitbox::Shapes(shapes) => Box::new(shapes.iter()),
+            Hitbox::Frames { frames } => Box::new(frames.iter().flatten()),
+        }
+    }
+
+    pub fn triangles_count(&self) -> usize {
+        self.shapes().map(Vec::len).sum()
+    }
+
+    /// Short description of the hitbox, like "3 shapes (12 triangles)"
+    pub fn summary(&self) -> String {
+        match self {
+            Hitbox::Shapes(shapes) => format!(
+                "{} shapes ({} triangles)",
+                shapes.len(),
+                self.triangles_count()
+            ),
+            Hitbox::Frames { frames } => format!(
+                "{} frames ({} triangles)",
+                frames.len(),
+                self.triangles_count()
+            ),
+        }
+    }
+}
+
+/// Compute the hitbox of an asset, the time spent in each stage is added to `stats`
+pub fn compute(
+    file: &Path,
+    settings: &HitboxSettings,
+    stats: &mut Stats,
+) -> Result<Hitbox, HitboxError> {
+    let start = Instant::now();
+    let image = image::open(file)?.to_rgba8();
+    stats.decode += start.elapsed();
+
+    let Some(slicing) = &settings.slicing else {
+        return Ok(Hitbox::Shapes(shapes(&image, settings, stats)?));
+    };
+
+    let frames = slicing
+        .frames(file, image.dimensions())
+        .map_err(HitboxError::Frames)?;
+
+    let frames = frames
+        .iter()
+        .enumerate()
+        .map(|(i, frame)| {
+            let (width, height) = if frame.rotated {
+                (frame.height, frame.width)
+            } else {
+                (frame.width, frame.height)
+            };
+            let mut region = imageops::crop_imm(&image, frame.x, frame.y, width, height).to_image();
+            if frame.rotated {
+                region = imageops::rotate270(&region);
+            }
+
+            match shapes(&region, settings, stats) {
+                // Empty frames are common in sprite sheets
+                Err(HitboxError::EmptyPolygon) => Ok(vec![]),
+                Err(e) => Err(e),
+                Ok(shapes) => shapes
+                    .into_iter()
+                    .map(|shape| {
+                        offset(shape, frame.offset).ok_or_else(|| {
+                            HitboxError::Frames(format!("the offset of frame {i} is too large"))
+                        })
+                    })
+                    .collect(),
+            }
+        })
+        .collect::<Result<_, _>>()?;
+
+    Ok(Hitbox::Frames { frames })
+}
+
+/// Compute the shapes of an image, one per connected opaque component
+fn shapes(
+    image: &RgbaImage,
+    settings: &HitboxSettings,
+    stats: &mut Stats,
+) -> Result<Vec<Shape>, HitboxError> {
+    let start = Instant::now();
+    let mut opaque: Grid<bool> = image
+        .rows()
+        .map(|row| {
+            row.map(|pixel| pixel.0[3] > settings.alpha_threshold)
+                .collect()
+        })
+        .collect();
+
+    // More passes than the largest side of the image change nothing, the image is empty or full by then
+    let max_passes = image.width().max(image.height());
+    for _ in 0..settings.erode.min(max_passes) {
+        opaque = morphology_pass(&opaque, false);
+    }
+    for _ in 0..settings.dilate.min(max_passes) {
+        opaque = morphology_pass(&opaque, true);
+    }
+
+    let outlines = outlines(&opaque);
+    stats.edges += start.elapsed();
+
+    let mut shapes = vec![];
+
+    for outline in outlines {
+        let start = Instant::now();
+        stats.vertices_before +=
+            outline.exterior.len() + outline.holes.iter().map(Vec::len).sum::<usize>();
+        let exterior = rdp(&outline.exterior, settings.epsilon);
+
+        // Components too small to make a polygon are ignored
+        if exterior.len() < 3 {
+            stats.rdp += start.elapsed();
+            continue;
+        }
+
+        let holes: Vec<Polygon> = outline
+            .holes
+            .iter()
+            .map(|hole| rdp(hole, settings.epsilon))
+            .filter(|hole| hole.len() >= 3)
+            .collect();
+        stats.vertices_after += exterior.len() + holes.iter().map(Vec::len).sum::<usize>();
+        stats.rdp += start.elapsed();
+
+        let start = Instant::now();
+        shapes.push(triangulate_with_holes(exterior, holes)?);
+        stats.triangulation += start.elapsed();
+    }
+
+    if shapes.is_empty() {
+        return Err(HitboxError::EmptyPolygon);
+    }
+
+    Ok(shapes)
+}
+
+/// Spread the pixels whose opacity is `opaque` to their 8 neighbours
+/// The outside of the image is transparent: the erosion eats the shapes touching the borders, and the dilation
+/// can't grow the shapes past the borders
+fn morphology_pass(grid: &Grid<bool>, opaque: bool) -> Grid<bool> {
+    let at = |x: i64, y: i64| -> bool {
+        if x < 0 || y < 0 {
+            return false;
+        }
+        grid.get(y as usize)
+            .and_then(|row| row.get(x as usize))
+            .copied()
+            .unwrap_or(false)
+    };
+
+    grid.iter()
+        .enumerate()
+        .map(|(y, row)| {
+            (0..row.len())
+                .map(|x| {
+                    let (x, y) = (x as i64, y as i64);
+                    let spreads = (-1..=1)
+                        .flat_map(|dy| (-1..=1).map(move |dx| (dx, dy)))
+                        .any(|(dx, dy)| at(x + dx, y + dy) == opaque);
+                    if spreads {
+                        opaque
+                    } else {
+                        !opaque
+                    }
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Move the shape of a trimmed frame to its position in the original frame, `None` when it overflows the coordinates
+fn offset(shape: Shape, (dx, dy): (u32, u32)) -> Option<Shape> {
+    let (dx, dy) = (u16::try_from(dx).ok()?, u16::try_from(dy).ok()?);
+    let offset = |point: Point| {
+        Some(Point::new(
+            point.x.checked_add(dx)?,
+            point.y.checked_add(dy)?,
+        ))
+    };
+    shape
+        .into_iter()
+        .map(|triangle| {
+            Some(Triangle(
+                offset(triangle.0)?,
+                offset(triangle.1)?,
+                offset(triangle.2)?,
+            ))
+        })
+        .collect()
+}
+
+fn triangulate_with_holes(exterior: Polygon, holes: Vec<Polygon>) -> Result<Shape, HitboxError> {
+    let mut points = exterior;
+    let mut hole_indices = vec![];
+
+    for hole in holes {
+        hole_indices.push(points.len());
+        points.extend(hole);
+    }
+
+    let coordinates: Vec<f64> = points
+        .iter()
+        .flat_map(|point| [point.x as f64, point.y as f64])
+        .collect();
+
+    Ok(earcut(&coordinates, &hole_indices, 2)?
+        .chunks(3)
+        .map(|chunk| Triangle(points[chunk[0]], points[chunk[1]], points[chunk[2]]))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid(rows: &[&str]) -> Grid<bool> {
+        rows.iter()
+            .map(|row| row.chars().map(|pixel| pixel == '#').collect())
+            .collect()
+    }
+
+    fn settings(erode: u32, dilate: u32) -> HitboxSettings {
+        HitboxSettings {
+            epsilon: 0.0,
+            alpha_threshold: 0,
+            erode,
+            dilate,
+            slicing: None,
+        }
+    }
+
+    /// An image with an opaque pixel at each of the points
+    fn image(size: u32, opaque: &[(u32, u32)]) -> RgbaImage {
+        let mut image = RgbaImage::new(size, size);
+        for &(x, y) in opaque {
+            image.put_pixel(x, y, image::Rgba([0, 0, 0, 255]));
+        }
+        image
+    }
+
+    #[test]
+    fn erosion_and_dilation() {
+        let square = grid(&[".....", ".###.", ".###.", ".###.", "....."]);
+        let eroded = morphology_pass(&square, false);
+        assert_eq!(eroded, grid(&[".....", ".....", "..#..", ".....", "....."]));
+        assert_eq!(morphology_pass(&eroded, true), square);
+
+        // The outside of the image is transparent
+        let full = grid(&["###", "###", "###"]);
+        assert_eq!(morphology_pass(&full, false), grid(&["...", ".#.", "..."]));
+        assert_eq!(morphology_pass(&full, true), full);
+    }
+
+    #[test]
+    fn passes_are_capped_to_the_image_size() {
+        let image = image(8, &[(3, 3), (4, 3), (3, 4), (4, 4)]);
+
+        let start = Instant::now();
+        assert!(matches!(
+            shapes(&image, &settings(u32::MAX, 0), &mut Stats::default()),
+            Err(HitboxError::EmptyPolygon)
+        ));
+
+        // The dilation fills the whole image
+        let shapes = shapes(&image, &settings(0, u32::MAX), &mut Stats::default()).unwrap();
+        assert_eq!(shapes.len(), 1);
+        let corners: Vec<(u16, u16)> = shapes[0]
+            .iter()
+            .flat_map(|triangle| [triangle.0, triangle.1, triangle.2])
+            .map(|point| (point.x, point.y))
+            .collect();
+        assert!(corners.contains(&(0, 0)) && corners.contains(&(7, 7)));
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn offsets_overflowing_the_coordinates() {
+        let shape = vec![Triangle(
+            Point::new(0, 0),
+            Point::new(10, 0),
+            Point::new(0, 65_000),
+        )];
+
+        let moved = offset(shape.clone(), (2, 3)).unwrap();
+        assert_eq!(
+            (moved[0].0, moved[0].1, moved[0].2),
+            (Point::new(2, 3), Point::new(12, 3), Point::new(2, 65_003))
+        );
+        assert!(offset(shape.clone(), (0, 1_000)).is_none());
+        assert!(offset(shape, (70_000, 0)).is_none());
+    }
+}
@@ -1,12 +1,27 @@
-use cprint::ceprintln;
+use report::reporter;
+use std::path::Path;
 use std::process::ExitCode;
 
+mod cache;
 mod cli;
 mod config;
+mod diagnostic;
+mod hitbox;
+mod migration;
+mod outline;
+mod project;
+mod prompt;
+mod report;
+mod run_log;
+mod sheet;
+mod template;
 mod terminal;
 
 fn main() -> ExitCode {
-    let cli: cli::Cli = argh::from_env();
+    terminal::reset_sigpipe();
+
+    let cli = cli::Cli::from_env();
+    terminal::set_color(cli.color.unwrap_or_default());
 
     if cli.version {
         println!(
@@ -17,13 +32,50 @@ fn main() -> ExitCode {
         return ExitCode::SUCCESS;
     }
 
-    match cli.subcommand {
-        Some(subcommand) => subcommand.run(),
-        None => {
-            ceprintln!(
-                "Error no subcommand was given, use --help to see the available subcommands"
-            );
-            ExitCode::FAILURE
+    report::set_format(cli.message_format.unwrap_or_default());
+    match report::Verbosity::new(cli.quiet, cli.verbose) {
+        Ok(verbosity) => report::set_verbosity(verbosity),
+        Err(e) => {
+            reporter().error(&e);
+            return ExitCode::FAILURE;
         }
     }
+
+    let Some(subcommand) = cli.subcommand else {
+        // `-v` used to print the version
+        let hint = if cli.verbose > 0 {
+            " (-v is --verbose, the version is printed by -V)"
+        } else {
+            ""
+        };
+        let message =
+            format!("no subcommand was given, use --help to see the available subcommands{hint}");
+        reporter().error(&message);
+        return ExitCode::FAILURE;
+    };
+
+    let options = cli::GlobalOptions {
+        profile: cli.profile,
+        directory: cli.directory,
+        manifest_path: cli.manifest_path,
+    };
+
+    // A new project is created in the current directory, the other subcommands work from the subdirectories too
+    let search = !matches!(subcommand, cli::SubCommandEnum::Init(_));
+    if let Err(e) = options.enter_project(search) {
+        reporter().error(&e);
+        return ExitCode::FAILURE;
+    }
+
+    let cazan_directory = Path::new(".cazan");
+    if subcommand.is_logged() && cazan_directory.is_dir() {
+        if let Err(e) = run_log::start(cazan_directory) {
+            let message = format!("couldn't write the log of the run: {e}");
+            reporter().warning(&message);
+        }
+    }
+
+    let exit_code = subcommand.run(&options);
+    run_log::finish(exit_code == ExitCode::SUCCESS);
+    exit_code
 }
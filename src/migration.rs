@@ -0,0 +1,151 @@
+//! The versions of the layout of the config, and the migrations between them
+//! When a field of the config is renamed or moved, `CONFIG_VERSION` is bumped and a migration from the previous
+//! version is added to `MIGRATIONS`, so that `cazan migrate` upgrades the existing projects step by step
+//! The commands reading the project file upgrade the older configs in memory, and refuse the newer ones
+
+use crate::project::ConfigSource;
+
+use serde_json::{Map, Value};
+
+/// Version of the config layout written and understood by this version of cazan
+pub const CONFIG_VERSION: u64 = 2;
+
+/// A migration of the config from the version `from` to the next one
+struct Migration {
+    from: u64,
+    /// What the migration changes, shown by `cazan migrate`
+    description: &'static str,
+    migrate: fn(&mut Map<String, Value>),
+}
+
+/// The migrations, ordered by version
+const MIGRATIONS: &[Migration] = &[Migration {
+    from: 1,
+    // Version 1 is the layout of the configs written before `configVersion` existed, only the version is added
+    description: "add `configVersion`",
+    migrate: |_| {},
+}];
+
+/// The version of a config, the configs without `configVersion` are version 1
+pub fn version(config: &Value) -> Result<u64, String> {
+    match config.get("configVersion") {
+        None => Ok(1),
+        Some(version) => version
+            .as_u64()
+            .filter(|version| *version >= 1)
+            .ok_or_else(|| format!("`configVersion` must be a positive integer (got {version})")),
+    }
+}
+
+/// The version of a config, or an error if it is newer than this version of cazan
+pub fn understood_version(config: &Value, file_name: &str) -> Result<u64, String> {
+    let version = version(config)?;
+    if version > CONFIG_VERSION {
+        return Err(format!(
+            "{file_name} has the config version {version}, but this version of cazan only understands up to version \
+            {CONFIG_VERSION}. Update cazan to use this project"
+        ));
+    }
+    Ok(version)
+}
+
+/// Upgrade a config to `CONFIG_VERSION`, returns the descriptions of the migrations applied
+pub fn migrate(config: &mut Value, file_name: &str) -> Result<Vec<&'static str>, String> {
+    let version = understood_version(config, file_name)?;
+    let Some(fields) = config.as_object_mut() else {
+        return Err("the config must be an object".to_string());
+    };
+
+    let applied = MIGRATIONS
+        .iter()
+        .filter(|migration| migration.from >= version)
+        .map(|migration| {
+            (migration.migrate)(fields);
+            migration.description
+        })
+        .collect();
+
+    if version < CONFIG_VERSION {
+        let config_version = Value::from(CONFIG_VERSION);
+        if fields.contains_key("configVersion") {
+            fields.insert("configVersion".to_string(), config_version);
+        } else {
+            // At the top of the config, after `$schema`
+            let index = fields
+                .keys()
+                .position(|key| key == "$schema")
+                .map_or(0, |i| i + 1);
+            fields.shift_insert(index, "configVersion".to_string(), config_version);
+        }
+    }
+
+    Ok(applied)
+}
+
+/// Upgrade the config of a project file in memory, returns the upgraded config with the version of the file when it
+/// was older. The configs that aren't valid JSON are returned as is, so that their parser reports the problem
+pub fn upgrade(source: ConfigSource) -> Result<(ConfigSource, Option<u64>), String> {
+    let Ok(mut config) = serde_json::from_str::<Value>(&source.json) else {
+        return Ok((source, None));
+    };
+
+    let version = understood_version(&config, &source.file)?;
+    if version == CONFIG_VERSION {
+        return Ok((source, None));
+    }
+
+    migrate(&mut config, &source.file)?;
+    // The diagnostics locate the problems of the upgraded config by their path
+    let json = serde_json::to_string_pretty(&config).unwrap();
+    Ok((source.migrated(json), Some(version)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn versions() {
+        assert_eq!(version(&json!({ "name": "game" })), Ok(1));
+        assert_eq!(version(&json!({ "configVersion": 2 })), Ok(2));
+        assert!(version(&json!({ "configVersion": 0 })).is_err());
+        assert!(version(&json!({ "configVersion": "2" })).is_err());
+
+        let newer = json!({ "configVersion": CONFIG_VERSION + 1 });
+        assert!(understood_version(&newer, "cazan.json")
+            .unwrap_err()
+            .starts_with("cazan.json has the config version"));
+    }
+
+    #[test]
+    fn version_is_added_after_the_schema() {
+        let mut config = json!({ "$schema": "./.cazan/schema.json", "name": "game" });
+        assert_eq!(
+            migrate(&mut config, "cazan.json"),
+            Ok(vec!["add `configVersion`"])
+        );
+        let keys: Vec<&String> = config.as_object().unwrap().keys().collect();
+        assert_eq!(keys, ["$schema", "configVersion", "name"]);
+        assert_eq!(config["configVersion"], CONFIG_VERSION);
+
+        // An up-to-date config is left as is
+        let migrated = config.clone();
+        assert_eq!(migrate(&mut config, "cazan.json"), Ok(vec![]));
+        assert_eq!(config, migrated);
+    }
+
+    #[test]
+    fn upgrade_in_memory() {
+        let source = ConfigSource::json("cazan.json", r#"{ "name": "game" }"#.to_string());
+        let (upgraded, version) = upgrade(source).unwrap();
+        assert_eq!(version, Some(1));
+        let config: Value = serde_json::from_str(&upgraded.json).unwrap();
+        assert_eq!(config["configVersion"], CONFIG_VERSION);
+
+        // The invalid configs are left to their parser
+        let source = ConfigSource::json("cazan.json", "{".to_string());
+        let (source, version) = upgrade(source).unwrap();
+        assert_eq!((source.json.as_str(), version), ("{", None));
+    }
+}
@@ -0,0 +1,243 @@
+//! Extraction of the outlines of an image
+//! Unlike `ImageEdgesParser::as_polygon`, which follows a single outline, every connected opaque component
+//! of the image gives an outline, with the transparent holes it encloses
+
+use std::collections::{HashSet, VecDeque};
+
+use cazan_common::geometry::{Point, Polygon};
+use cazan_common::image::Grid;
+
+/// The 8 neighbours of a pixel, clockwise (the y axis points down) starting from the west
+const DIRECTIONS: [(i32, i32); 8] = [
+    (-1, 0),
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+    (1, 0),
+    (1, 1),
+    (0, 1),
+    (-1, 1),
+];
+
+/// The outline of a connected opaque component
+#[derive(Debug)]
+pub struct Outline {
+    pub exterior: Polygon,
+    pub holes: Vec<Polygon>,
+}
+
+/// Label of each pixel, `None` for the pixels not belonging to any region
+struct Labels {
+    labels: Grid<Option<usize>>,
+    /// First pixel of each region in raster order
+    starts: Vec<(i32, i32)>,
+}
+
+impl Labels {
+    fn get(&self, (x, y): (i32, i32)) -> Option<usize> {
+        if x < 0 || y < 0 {
+            return None;
+        }
+        self.labels
+            .get(y as usize)
+            .and_then(|row| row.get(x as usize))
+            .copied()
+            .flatten()
+    }
+}
+
+/// Outlines of the opaque components of the grid, in raster order of their top-left pixel
+pub fn outlines(opaque: &Grid<bool>) -> Vec<Outline> {
+    let components = label(opaque, true, &DIRECTIONS);
+    // Transparent regions are 4-connected, so that they don't cross the diagonals of the opaque components
+    let transparent = label(opaque, false, &[(-1, 0), (0, -1), (1, 0), (0, 1)]);
+
+    let mut outlines: Vec<Outline> = components
+        .starts
+        .iter()
+        .enumerate()
+        .map(|(id, &start)| Outline {
+            exterior: trace(start, |pixel| components.get(pixel) == Some(id)),
+            holes: vec![],
+        })
+        .collect();
+
+    // The transparent regions touching the border of the image are outside of every component
+    let mut outside = vec![false; transparent.starts.len()];
+    let height = transparent.labels.len();
+    for (y, row) in transparent.labels.iter().enumerate() {
+        for (x, label) in row.iter().enumerate() {
+            if let Some(id) = label {
+                if x == 0 || y == 0 || x == row.len() - 1 || y == height - 1 {
+                    outside[*id] = true;
+                }
+            }
+        }
+    }
+
+    for (id, &(x, y)) in transparent.starts.iter().enumerate() {
+        if outside[id] {
+            continue;
+        }
+
+        // The first pixel of a hole in raster order has the enclosing component on its left
+        if let Some(owner) = components.get((x - 1, y)) {
+            outlines[owner]
+                .holes
+                .push(trace((x, y), |pixel| transparent.get(pixel) == Some(id)));
+        }
+    }
+
+    outlines
+}
+
+/// Label the connected regions of the pixels whose opacity is `opaque`
+fn label(grid: &Grid<bool>, opaque: bool, neighbours: &[(i32, i32)]) -> Labels {
+    let mut labels: Grid<Option<usize>> = grid.iter().map(|row| vec![None; row.len()]).collect();
+    let mut starts = vec![];
+
+    for y in 0..grid.len() {
+        for x in 0..grid[y].len() {
+            if grid[y][x] != opaque || labels[y][x].is_some() {
+                continue;
+            }
+
+            let id = starts.len();
+            starts.push((x as i32, y as i32));
+            labels[y][x] = Some(id);
+
+            let mut queue = VecDeque::from([(x as i32, y as i32)]);
+            while let Some((px, py)) = queue.pop_front() {
+                for (dx, dy) in neighbours {
+                    let (nx, ny) = (px + dx, py + dy);
+                    if nx < 0 || ny < 0 || ny as usize >= grid.len() {
+                        continue;
+                    }
+                    let (nx, ny) = (nx as usize, ny as usize);
+                    if nx >= grid[ny].len() || grid[ny][nx] != opaque || labels[ny][nx].is_some() {
+                        continue;
+                    }
+                    labels[ny][nx] = Some(id);
+                    queue.push_back((nx as i32, ny as i32));
+                }
+            }
+        }
+    }
+
+    Labels { labels, starts }
+}
+
+/// Follow the border of a region with the Moore-neighbour tracing, starting from its first pixel in raster order
+fn trace(start: (i32, i32), inside: impl Fn((i32, i32)) -> bool) -> Polygon {
+    let mut contour = vec![start];
+    let mut current = start;
+    // The pixel on the left of the first pixel is outside of the region
+    let mut backtrack = 0;
+    let mut states = HashSet::from([(start, backtrack)]);
+
+    loop {
+        let next = (1..=8).map(|k| (backtrack + k) % 8).find_map(|direction| {
+            let (dx, dy) = DIRECTIONS[direction];
+            let pixel = (current.0 + dx, current.1 + dy);
+            inside(pixel).then_some((pixel, direction))
+        });
+
+        let Some((next, direction)) = next else {
+            // Isolated pixel
+            break;
+        };
+
+        if current == start && contour.len() > 1 && Some(&next) == contour.get(1) {
+            contour.pop();
+            break;
+        }
+
+        // The last neighbour checked before `next` is outside, it is the backtrack of `next`
+        let (dx, dy) = DIRECTIONS[(direction + 7) % 8];
+        let outside = (current.0 + dx - next.0, current.1 + dy - next.1);
+        backtrack = DIRECTIONS.iter().position(|&d| d == outside).unwrap();
+
+        // Guard against the configurations where the stopping criterion is never met
+        if !states.insert((next, backtrack)) {
+            break;
+        }
+
+        contour.push(next);
+        current = next;
+    }
+
+    contour
+        .into_iter()
+        .map(|(x, y)| Point::new(x as u16, y as u16))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid(rows: &[&str]) -> Grid<bool> {
+        rows.iter()
+            .map(|row| row.chars().map(|pixel| pixel == '#').collect())
+            .collect()
+    }
+
+    fn points(polygon: &Polygon) -> Vec<(u16, u16)> {
+        polygon.iter().map(|point| (point.x, point.y)).collect()
+    }
+
+    #[test]
+    fn exterior_is_traced_clockwise_from_the_top_left_pixel() {
+        let traced = outlines(&grid(&[".....", ".###.", ".###.", ".###.", "....."]));
+        assert_eq!(traced.len(), 1);
+        assert_eq!(
+            points(&traced[0].exterior),
+            [
+                (1, 1),
+                (2, 1),
+                (3, 1),
+                (3, 2),
+                (3, 3),
+                (2, 3),
+                (1, 3),
+                (1, 2)
+            ]
+        );
+        assert!(traced[0].holes.is_empty());
+
+        let dot = outlines(&grid(&["...", ".#.", "..."]));
+        assert_eq!(points(&dot[0].exterior), [(1, 1)]);
+    }
+
+    #[test]
+    fn components_are_in_raster_order() {
+        // The diagonal joins the pixels of the first component
+        let traced = outlines(&grid(&["#...#", ".#..#", "....."]));
+        assert_eq!(traced.len(), 2);
+        assert_eq!(points(&traced[0].exterior), [(0, 0), (1, 1)]);
+        assert_eq!(points(&traced[1].exterior), [(4, 0), (4, 1)]);
+    }
+
+    #[test]
+    fn holes_belong_to_their_enclosing_component() {
+        let traced = outlines(&grid(&[
+            "#######", "#.....#", "#.###.#", "#.#.#.#", "#.###.#", "#.....#", "#######",
+        ]));
+        assert_eq!(traced.len(), 2);
+        assert_eq!(traced[0].holes.len(), 1);
+        assert_eq!(traced[0].holes[0].len(), 16);
+        assert_eq!(points(&traced[1].holes[0]), [(3, 3)]);
+    }
+
+    #[test]
+    fn holes_are_4_connected_and_closed() {
+        // The two transparent pixels only touch by a corner, they are two holes
+        let traced = outlines(&grid(&["####", "#.##", "##.#", "####"]));
+        let holes: Vec<Vec<(u16, u16)>> = traced[0].holes.iter().map(points).collect();
+        assert_eq!(holes, [[(1, 1)], [(2, 2)]]);
+
+        // A gap open on the border of the image isn't a hole
+        let gap = outlines(&grid(&["###", "#..", "###"]));
+        assert!(gap[0].holes.is_empty());
+    }
+}
@@ -0,0 +1,550 @@
+//! The project file, `cazan.json`, `cazan.toml` or `cazan.yaml`
+//! The TOML and YAML files are converted to JSON when they are read, so that the config is always parsed from JSON
+//! and the locked config stays in JSON for the engines
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::diagnostic::Diagnostic;
+
+use serde::Serialize;
+use serde_json::Value;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Format {
+    Json,
+    Toml,
+    Yaml,
+}
+
+/// The formats of the project file, in the order they are looked for
+pub const FORMATS: [Format; 3] = [Format::Json, Format::Toml, Format::Yaml];
+
+impl Format {
+    /// Name of the project file in this format
+    pub fn file_name(self) -> &'static str {
+        match self {
+            Format::Json => "cazan.json",
+            Format::Toml => "cazan.toml",
+            Format::Yaml => "cazan.yaml",
+        }
+    }
+
+    /// The format of a project file from its name
+    pub fn from_file_name(path: &Path) -> Option<Self> {
+        FORMATS
+            .into_iter()
+            .find(|format| path.file_name() == Some(format.file_name().as_ref()))
+    }
+}
+
+/// The root of the project containing `directory`, the closest directory with a project file, like cargo does
+pub fn find_root(directory: &Path) -> Option<&Path> {
+    directory.ancestors().find(|directory| {
+        FORMATS
+            .iter()
+            .any(|format| directory.join(format.file_name()).is_file())
+    })
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format.to_lowercase().as_str() {
+            "json" => Ok(Format::Json),
+            "toml" => Ok(Format::Toml),
+            "yaml" | "yml" => Ok(Format::Yaml),
+            _ => Err(format!(
+                "unknown format `{format}`, expected json, toml or yaml"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Format::Json => "JSON",
+            Format::Toml => "TOML",
+            Format::Yaml => "YAML",
+        })
+    }
+}
+
+/// The project file of a directory
+pub struct ProjectFile {
+    pub path: PathBuf,
+    pub format: Format,
+}
+
+impl ProjectFile {
+    /// Find the project file of the directory, `Ok(None)` if there is none and an error if there are several
+    pub fn find(directory: &Path) -> Result<Option<Self>, String> {
+        let files: Vec<ProjectFile> = FORMATS
+            .iter()
+            .map(|format| ProjectFile {
+                path: directory.join(format.file_name()),
+                format: *format,
+            })
+            .filter(|file| file.path.exists())
+            .collect();
+
+        if files.len() > 1 {
+            return Err(format!(
+                "several project files were found ({}), only one of them must be kept",
+                files
+                    .iter()
+                    .map(|file| file.name())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+
+        Ok(files.into_iter().next())
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.format.file_name()
+    }
+
+    /// Read the file, converted to JSON
+    pub fn read(&self) -> Result<ConfigSource, ReadError> {
+        let text = fs::read_to_string(&self.path).map_err(ReadError::Io)?;
+        ConfigSource::convert(self.name(), self.format, text).map_err(ReadError::Syntax)
+    }
+}
+
+/// Why a project file couldn't be read
+pub enum ReadError {
+    Io(std::io::Error),
+    /// The TOML or YAML file is invalid
+    Syntax(Box<Diagnostic>),
+}
+
+/// The contents of a config file, converted to JSON
+pub struct ConfigSource {
+    /// Name of the file, as shown in the diagnostics
+    pub file: String,
+    pub json: String,
+    /// The text of the JSON file the config comes from, the diagnostics show its snippets. It is `json` itself unless
+    /// the config was migrated in memory, the values are then located in it by their path
+    pub text: Option<String>,
+}
+
+impl ConfigSource {
+    /// A JSON config file
+    pub fn json(file: &str, json: String) -> Self {
+        Self {
+            file: file.to_string(),
+            text: Some(json.clone()),
+            json,
+        }
+    }
+
+    /// A config that isn't written in its file yet, such as an edited one
+    pub fn unsaved(file: &str, json: String) -> Self {
+        Self {
+            file: file.to_string(),
+            json,
+            text: None,
+        }
+    }
+
+    /// The config migrated in memory, the snippets are still those of the file
+    pub fn migrated(self, json: String) -> Self {
+        Self { json, ..self }
+    }
+
+    /// Convert a config file to JSON, the syntax errors of the TOML and YAML files are returned as diagnostics
+    pub fn convert(file: &str, format: Format, text: String) -> Result<Self, Box<Diagnostic>> {
+        let value: Value = match format {
+            Format::Json => return Ok(Self::json(file, text)),
+            Format::Toml => toml::from_str(&text).map_err(|e| {
+                let span = e.span().map(|span| (span.start, span.end));
+                Box::new(Diagnostic::syntax(file, &text, span, e.message()))
+            })?,
+            Format::Yaml => serde_norway::from_str(&text).map_err(|e| {
+                let span = e
+                    .location()
+                    .map(|location| (location.index(), location.index() + 1));
+                let message = e.to_string();
+                // The location is shown in the snippet
+                let message = match message.find(" at line ") {
+                    Some(i) => &message[..i],
+                    None => &message,
+                };
+                Box::new(Diagnostic::syntax(file, &text, span, message))
+            })?,
+        };
+
+        Ok(Self::unsaved(
+            file,
+            serde_json::to_string_pretty(&value).unwrap(),
+        ))
+    }
+}
+
+/// Write a config in a format, the `null` values are left out since TOML can't represent them
+pub fn to_format(config: &impl Serialize, format: Format) -> Result<String, String> {
+    let mut value = serde_json::to_value(config).map_err(|e| e.to_string())?;
+    remove_nulls(&mut value);
+
+    match format {
+        Format::Json => serde_json::to_string_pretty(&value).map_err(|e| e.to_string()),
+        Format::Toml => toml::to_string_pretty(&value).map_err(|e| e.to_string()),
+        Format::Yaml => serde_norway::to_string(&value).map_err(|e| e.to_string()),
+    }
+}
+
+fn remove_nulls(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            map.retain(|_, value| !value.is_null());
+            map.values_mut().for_each(remove_nulls);
+        }
+        Value::Array(values) => values.iter_mut().for_each(remove_nulls),
+        _ => {}
+    }
+}
+
+/// Apply the changes from `previous` to `edited` to the text of a TOML file, the comments, the spacing and the order
+/// of the keys that didn't change are kept
+pub fn edit_toml(text: &str, previous: &Value, edited: &Value) -> Result<String, String> {
+    let mut document: toml_edit::DocumentMut = text.parse().map_err(|e| format!("{e}"))?;
+    edit_toml_item(document.as_item_mut(), previous, edited, false);
+    Ok(document.to_string())
+}
+
+/// `inline` is true inside the values (lists and inline tables), where the objects can't be written as tables
+fn edit_toml_item(item: &mut toml_edit::Item, previous: &Value, edited: &Value, inline: bool) {
+    if previous == edited {
+        return;
+    }
+
+    match (previous, edited) {
+        (Value::Object(previous), Value::Object(edited)) if item.is_table_like() => {
+            let inline = inline || item.is_inline_table();
+            let table = item.as_table_like_mut().unwrap();
+            for key in previous.keys() {
+                if edited.get(key).is_none_or(Value::is_null) {
+                    table.remove(key);
+                }
+            }
+            for (key, value) in edited {
+                match (table.get_mut(key), previous.get(key)) {
+                    (Some(child), Some(previous)) => edit_toml_item(child, previous, value, inline),
+                    _ => {
+                        if let Some(child) = to_toml_item(value, inline) {
+                            table.insert(key, child);
+                        }
+                    }
+                }
+            }
+        }
+        (Value::Array(previous), Value::Array(edited)) if item.is_array() => {
+            let array = item.as_array_mut().unwrap();
+            for (i, (previous, edited)) in previous.iter().zip(edited).enumerate() {
+                let value = array.get_mut(i).unwrap();
+                let mut child = toml_edit::Item::Value(std::mem::replace(value, false.into()));
+                edit_toml_item(&mut child, previous, edited, true);
+                *value = child.into_value().unwrap();
+            }
+            while array.len() > edited.len() {
+                array.remove(array.len() - 1);
+            }
+            for value in &edited[previous.len().min(edited.len())..] {
+                let Some(mut value) = to_toml_value(value) else {
+                    continue;
+                };
+                // In the multiline lists the new items are on their own line, like the last one
+                let last = array.iter().last().map(|last| last.decor().clone());
+                if let Some(decor) = last.filter(|decor| {
+                    decor
+                        .prefix()
+                        .and_then(|prefix| prefix.as_str())
+                        .is_some_and(|prefix| prefix.contains('\n'))
+                }) {
+                    *value.decor_mut() = decor;
+                }
+                array.push_formatted(value);
+            }
+        }
+        (Value::Array(previous), Value::Array(edited))
+            if item.is_array_of_tables() && edited.iter().all(Value::is_object) =>
+        {
+            let tables = item.as_array_of_tables_mut().unwrap();
+            for (i, (previous, edited)) in previous.iter().zip(edited).enumerate() {
+                let table = tables.get_mut(i).unwrap();
+                let mut child = toml_edit::Item::Table(std::mem::take(table));
+                edit_toml_item(&mut child, previous, edited, false);
+                *table = child.into_table().unwrap();
+            }
+            while tables.len() > edited.len() {
+                tables.remove(tables.len() - 1);
+            }
+            for value in &edited[previous.len().min(edited.len())..] {
+                if let Some(toml_edit::Item::Table(table)) = to_toml_item(value, false) {
+                    tables.push(table);
+                }
+            }
+        }
+        _ => {
+            let Some(mut new_item) = to_toml_item(edited, inline || item.is_value()) else {
+                *item = toml_edit::Item::None;
+                return;
+            };
+            // The comments after the value stay on its line
+            if let (Some(previous), Some(new_value)) = (item.as_value(), new_item.as_value_mut()) {
+                *new_value.decor_mut() = previous.decor().clone();
+            }
+            *item = new_item;
+        }
+    }
+}
+
+/// The objects are tables, unless they are `inline`. `null` has no TOML value
+fn to_toml_item(value: &Value, inline: bool) -> Option<toml_edit::Item> {
+    match value {
+        Value::Object(fields) if !inline => {
+            let mut table = toml_edit::Table::new();
+            for (key, value) in fields {
+                if let Some(item) = to_toml_item(value, false) {
+                    table.insert(key, item);
+                }
+            }
+            // A table only made of tables has no header of its own (ex: `[profiles.dev]` without `[profiles]`)
+            table.set_implicit(true);
+            Some(toml_edit::Item::Table(table))
+        }
+        value => to_toml_value(value).map(toml_edit::Item::Value),
+    }
+}
+
+fn to_toml_value(value: &Value) -> Option<toml_edit::Value> {
+    Some(match value {
+        Value::Null => return None,
+        Value::Bool(bool) => (*bool).into(),
+        Value::Number(number) => match number.as_i64() {
+            Some(integer) => integer.into(),
+            None => number.as_f64()?.into(),
+        },
+        Value::String(string) => string.into(),
+        Value::Array(values) => {
+            toml_edit::Value::Array(values.iter().filter_map(to_toml_value).collect())
+        }
+        Value::Object(fields) => toml_edit::Value::InlineTable(
+            fields
+                .iter()
+                .filter_map(|(key, value)| Some((key, to_toml_value(value)?)))
+                .collect(),
+        ),
+    })
+}
+
+/// Whether a YAML text has comments, which are lost when the file is written again
+/// The `#` starting a comment is at the start of a line or after a space, outside of the quoted strings
+pub fn has_yaml_comments(text: &str) -> bool {
+    text.lines().any(|line| {
+        let mut quote = None;
+        let mut previous = ' ';
+        for c in line.chars() {
+            match (quote, c) {
+                (None, '#') if previous == ' ' || previous == '\t' => return true,
+                (None, '"' | '\'') => quote = Some(c),
+                (Some(open), c) if c == open && previous != '\\' => quote = None,
+                _ => {}
+            }
+            previous = c;
+        }
+        false
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{AssetConfig, Config};
+
+    fn parse(format: Format, text: &str) -> Config {
+        let source = ConfigSource::convert(format.file_name(), format, text.to_string()).unwrap();
+        Config::parse(&source.json).unwrap().0
+    }
+
+    #[test]
+    fn convert_toml_with_escapes() {
+        let config = parse(
+            Format::Toml,
+            r#"
+name = 'my "game"'
+version = "1.0.0"
+authors = ["C:\\games"]
+assets = ['assets\*.png']
+"#,
+        );
+        assert_eq!(config.name, r#"my "game""#);
+        assert_eq!(config.authors, [r"C:\games"]);
+        assert_eq!(config.assets.unwrap()[0].pattern(), r"assets\*.png");
+    }
+
+    #[test]
+    fn convert_yaml_with_escapes() {
+        let config = parse(
+            Format::Yaml,
+            r#"
+name: my "game"
+version: 1.0.0
+authors: ["C:\\games"]
+assets:
+  - assets\*.png
+  - pattern: sheets/*.png
+    epsilon: 2
+"#,
+        );
+        assert_eq!(config.name, r#"my "game""#);
+        assert_eq!(config.authors, [r"C:\games"]);
+        let assets = config.assets.unwrap();
+        assert_eq!(assets[0].pattern(), r"assets\*.png");
+        assert!(matches!(&assets[1], AssetConfig::Detailed(asset) if asset.epsilon == Some(2.)));
+    }
+
+    #[test]
+    fn syntax_errors_are_diagnostics() {
+        let error = ConfigSource::convert("cazan.toml", Format::Toml, "name = ".to_string());
+        assert!(error.is_err());
+        let error = ConfigSource::convert("cazan.yaml", Format::Yaml, "name: [".to_string());
+        assert!(error.is_err());
+    }
+
+    #[test]
+    fn round_trip_between_formats() {
+        let json =
+            r#"{"name":"a\\b","version":"1.0.0","authors":[],"assets":["*.png"],"plugins":null}"#;
+        let (config, _) = Config::parse(json).unwrap();
+
+        for format in FORMATS {
+            let text = to_format(&config, format).unwrap();
+            let converted = parse(format, &text);
+            assert_eq!(converted.name, r"a\b");
+            // `null` is left out, TOML can't represent it
+            assert!(converted.plugins.is_none());
+        }
+    }
+
+    fn edit(text: &str, change: impl FnOnce(&mut Value)) -> String {
+        let previous: Value = toml::from_str(text).unwrap();
+        let mut edited = previous.clone();
+        change(&mut edited);
+        let result = edit_toml(text, &previous, &edited).unwrap();
+        assert_eq!(toml::from_str::<Value>(&result).unwrap(), edited);
+        result
+    }
+
+    #[test]
+    fn edit_toml_keeps_the_comments() {
+        let text = r#"# keep me
+name = "game" # the name
+version = "1.0.0"
+authors = []
+
+assets = [
+    "a/*.png",
+    "b/*.png",
+]
+
+[profiles.dev] # fast builds
+rdpEpsilon = 6
+"#;
+        let result = edit(text, |value| {
+            value["version"] = "2.0.0".into();
+            value["assets"]
+                .as_array_mut()
+                .unwrap()
+                .push("c/*.png".into());
+            value["profiles"]["dev"]["erode"] = 1.into();
+            value["profiles"]["release"] = serde_json::json!({ "rdpEpsilon": 1.5 });
+        });
+
+        assert_eq!(
+            result,
+            r#"# keep me
+name = "game" # the name
+version = "2.0.0"
+authors = []
+
+assets = [
+    "a/*.png",
+    "b/*.png",
+    "c/*.png",
+]
+
+[profiles.dev] # fast builds
+rdpEpsilon = 6
+erode = 1
+
+[profiles.release]
+rdpEpsilon = 1.5
+"#
+        );
+    }
+
+    #[test]
+    fn edit_toml_single_line_lists() {
+        let text = "assets = [\"*.png\"]\n";
+        let result = edit(text, |value| {
+            value["assets"]
+                .as_array_mut()
+                .unwrap()
+                .push(r"x\*.png".into());
+        });
+        assert_eq!(result, "assets = [\"*.png\", 'x\\*.png']\n");
+    }
+
+    #[test]
+    fn edit_toml_removes_values() {
+        let text = "name = \"game\"\nauthors = [\"a\", \"b\"] # authors\nerode = 1\n";
+        let result = edit(text, |value| {
+            value["authors"].as_array_mut().unwrap().remove(0);
+            value.as_object_mut().unwrap().remove("erode");
+        });
+        assert_eq!(result, "name = \"game\"\nauthors = [\"b\"] # authors\n");
+    }
+
+    #[test]
+    fn edit_toml_arrays_of_tables() {
+        let text = "[[assets]]\npattern = \"a/*.png\" # sprites\n";
+        let result = edit(text, |value| {
+            value["assets"][0]["epsilon"] = 2.into();
+            value["assets"]
+                .as_array_mut()
+                .unwrap()
+                .push(serde_json::json!({ "pattern": "b/*.png" }));
+        });
+        assert_eq!(
+            result,
+            "[[assets]]\npattern = \"a/*.png\" # sprites\nepsilon = 2\n\n[[assets]]\npattern = \"b/*.png\"\n"
+        );
+    }
+
+    #[test]
+    fn yaml_comments() {
+        assert!(has_yaml_comments("# top\nname: game\n"));
+        assert!(has_yaml_comments("name: game # the name\n"));
+        assert!(!has_yaml_comments(
+            "name: \"#1 game\"\nassets: ['a#b/*.png']\n"
+        ));
+        assert!(!has_yaml_comments("name: game#1\n"));
+    }
+
+    #[test]
+    fn format_of_file_names() {
+        assert_eq!(
+            Format::from_file_name(Path::new("game/cazan.toml")),
+            Some(Format::Toml)
+        );
+        assert_eq!(Format::from_file_name(Path::new("cazan.yml")), None);
+        assert_eq!("YML".parse::<Format>(), Ok(Format::Yaml));
+    }
+}
@@ -0,0 +1,85 @@
+//! Interactive prompts on the terminal
+//! The answer is edited in raw mode, so that the default value can be shown and the prompt cancelled with Esc or Ctrl+C
+
+use std::io::{stdin, stdout, Write};
+
+use crate::report::{self, MessageFormat};
+
+use colored::Colorize;
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use crossterm::terminal;
+use crossterm::tty::IsTty;
+
+/// Whether the prompts can be shown, they need a terminal on stdin and stdout, and the human messages
+pub fn is_interactive() -> bool {
+    stdin().is_tty() && stdout().is_tty() && report::format() == MessageFormat::Human
+}
+
+/// Ask a question, returns the trimmed answer (empty if the user just pressed Enter) or `None` if cancelled
+pub fn ask(question: &str, default: &str) -> Option<String> {
+    let mut stdout = stdout();
+    if default.is_empty() {
+        print!("{} ", question.bold());
+    } else {
+        print!(
+            "{} {} ",
+            question.bold(),
+            format!("({default})").bright_black()
+        );
+    }
+    stdout.flush().ok()?;
+
+    terminal::enable_raw_mode().ok()?;
+    let answer = read_line(&mut stdout);
+    terminal::disable_raw_mode().ok()?;
+    print!("\r\n");
+    stdout.flush().ok()?;
+
+    answer.map(|answer| answer.trim().to_string())
+}
+
+/// Ask a question until the answer is empty or accepted by `parse`, `parse` returns the error to show otherwise
+pub fn ask_parsed<T>(
+    question: &str,
+    default: &str,
+    parse: impl Fn(&str) -> Result<T, String>,
+) -> Option<Option<T>> {
+    loop {
+        let answer = ask(question, default)?;
+        if answer.is_empty() {
+            return Some(None);
+        }
+        match parse(&answer) {
+            Ok(value) => return Some(Some(value)),
+            Err(e) => println!("{}", e.bright_yellow()),
+        }
+    }
+}
+
+fn read_line(stdout: &mut impl Write) -> Option<String> {
+    let mut answer = String::new();
+
+    loop {
+        let Event::Key(key) = event::read().ok()? else {
+            continue;
+        };
+        if key.kind == KeyEventKind::Release {
+            continue;
+        }
+
+        match key.code {
+            KeyCode::Enter => return Some(answer),
+            KeyCode::Esc => return None,
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return None,
+            KeyCode::Char(c) => {
+                answer.push(c);
+                write!(stdout, "{c}").ok()?;
+            }
+            KeyCode::Backspace if answer.pop().is_some() => {
+                write!(stdout, "\u{8} \u{8}").ok()?;
+            }
+            _ => {}
+        }
+        stdout.flush().ok()?;
+    }
+}
@@ -0,0 +1,773 @@
+//! The reporter of the subcommands, the messages and the progress of the prebuild go through it
+//! By default they are printed for humans, with colours and a progress area. With `--message-format json`, each one is
+//! a line of JSON on stdout, so that the build scripts and the editors don't have to parse the terminal output
+//!
+//! ```text
+//! {"event":"asset-started","file":"assets/player.png"}
+//! {"event":"asset-finished","file":"assets/player.png","status":"parsed","shapes":1,"triangles":14}
+//! {"event":"summary","assets":1,"parsed":1,"cached":0,"failed":0,"skipped":0,"duration":0.02}
+//! ```
+//!
+//! The diff of `migrate` is a `diff` event, and the values printed by `config get` and `config list` are `config-value`
+//! events. `-q` only keeps the errors and the values, `-v` adds the timings of the stages of each asset and `-vv` the expansion of the globs
+//! and the resolved config. All the events are also written to the log of the run, see `run_log`
+
+use std::fmt;
+use std::io::{stdout, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Mutex, OnceLock};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::config::Config;
+use crate::diagnostic::{Diagnostic, Level};
+use crate::hitbox::{Hitbox, HitboxError, Stats};
+use crate::run_log;
+use crate::terminal::SubTerminal;
+
+use colored::Colorize;
+use cprint::{ceprintln, cformat, cprintln};
+use crossterm::terminal;
+use crossterm::tty::IsTty;
+use serde_json::{json, Value};
+use similar::{ChangeTag, TextDiff};
+
+/// The events emitted with `--quiet` too: the errors, and the values asked for by `config get` and `config list`
+const QUIET_EVENTS: [&str; 2] = ["error", "config-value"];
+/// Lines of context around the changes of a diff
+const DIFF_CONTEXT: usize = 3;
+
+/// Maximum number of assets shown while they are parsed, so the progress area keeps a fixed height
+const MAX_ASSET_LINES: usize = 8;
+/// Number of characters of the progress bar, between its brackets
+const BAR_WIDTH: usize = 24;
+/// Time between two redraws of the progress
+const TICK: Duration = Duration::from_millis(200);
+
+static FORMAT: OnceLock<MessageFormat> = OnceLock::new();
+static VERBOSITY: OnceLock<Verbosity> = OnceLock::new();
+static HUMAN: HumanReporter = HumanReporter {
+    progress: Mutex::new(None),
+    ticker: Mutex::new(None),
+};
+static JSON: JsonReporter = JsonReporter {
+    destination: Destination::Stdout,
+};
+static LOG: JsonReporter = JsonReporter {
+    destination: Destination::Log,
+};
+
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum MessageFormat {
+    #[default]
+    Human,
+    Json,
+}
+
+impl FromStr for MessageFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "human" => Ok(Self::Human),
+            "json" => Ok(Self::Json),
+            _ => Err(format!(
+                "unknown message format `{s}`, expected human or json"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for MessageFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Human => write!(f, "human"),
+            Self::Json => write!(f, "json"),
+        }
+    }
+}
+
+/// Select the format of the messages, it can only be set once, before anything is reported
+pub fn set_format(format: MessageFormat) {
+    FORMAT.set(format).ok();
+}
+
+pub fn format() -> MessageFormat {
+    FORMAT.get().copied().unwrap_or_default()
+}
+
+/// How much is reported, selected with `-q`, `-v` and `-vv`
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Default)]
+pub enum Verbosity {
+    /// Only the errors
+    Quiet,
+    #[default]
+    Normal,
+    /// The timings of the stages of each asset, and the vertices saved by the simplification
+    Verbose,
+    /// The files matched by each glob pattern, and the resolved config
+    Debug,
+}
+
+impl Verbosity {
+    /// The verbosity of `-q` and of the number of `-v`
+    pub fn new(quiet: bool, verbose: u8) -> Result<Self, String> {
+        match (quiet, verbose) {
+            (true, 0) => Ok(Self::Quiet),
+            (true, _) => Err("--quiet and --verbose can't be used together".to_string()),
+            (false, 0) => Ok(Self::Normal),
+            (false, 1) => Ok(Self::Verbose),
+            (false, _) => Ok(Self::Debug),
+        }
+    }
+}
+
+/// Select the verbosity, it can only be set once, before anything is reported
+pub fn set_verbosity(verbosity: Verbosity) {
+    VERBOSITY.set(verbosity).ok();
+}
+
+pub fn verbosity() -> Verbosity {
+    VERBOSITY.get().copied().unwrap_or_default()
+}
+
+/// The reporter of the selected message format, the events are also written to the log of the run
+pub fn reporter() -> &'static dyn Reporter {
+    &LoggedReporter
+}
+
+fn output() -> &'static dyn Reporter {
+    match format() {
+        MessageFormat::Human => &HUMAN,
+        MessageFormat::Json => &JSON,
+    }
+}
+
+/// Counts of a prebuild, reported once its assets are parsed
+pub struct Summary {
+    pub assets: usize,
+    /// The assets parsed by this run, without the ones found in the cache
+    pub parsed: usize,
+    pub cached: usize,
+    pub failed: usize,
+    pub duration: Duration,
+}
+
+impl Summary {
+    /// The images without an alpha channel
+    fn skipped(&self) -> usize {
+        self.assets - self.parsed - self.cached - self.failed
+    }
+}
+
+/// The outcome of an asset of the prebuild
+#[derive(Clone, Copy)]
+pub enum AssetResult<'a> {
+    Parsed(&'a Hitbox, &'a Stats),
+    /// The hitbox was found in the cache, the asset wasn't parsed
+    Cached(&'a Hitbox),
+    Failed(&'a HitboxError),
+}
+
+pub trait Reporter: Sync {
+    /// A step of the subcommand, `label` is the first word of the message (ex: "Locked")
+    fn status(&self, label: &str, message: &str);
+    fn warning(&self, message: &str);
+    fn error(&self, message: &str);
+    fn diagnostic(&self, diagnostic: &Diagnostic);
+
+    /// `total` assets will be parsed by `workers` threads, the next asset events are about them
+    fn parsing_started(&self, total: usize, workers: usize);
+    fn asset_started(&self, file: &Path);
+    /// `checksum` is the one of the content of the file, the key of its hitbox in `.cazan/build/assets.json`
+    fn asset_finished(&self, file: &Path, checksum: &str, result: AssetResult);
+    fn parsing_finished(&self);
+    fn summary(&self, summary: &Summary);
+
+    /// The files matched by a glob pattern of the assets, reported with `-vv`
+    fn pattern_expanded(&self, pattern: &str, files: &[PathBuf]);
+    /// The config used once the profile, the environment and the command line are applied, reported with `-vv`
+    fn resolved_config(&self, config: &Config);
+
+    /// The changes of a file, `old` and `new` are its texts before and after
+    fn diff(&self, file: &str, old: &str, new: &str);
+    /// A value of the config asked for by `config get`
+    fn config_value(&self, key: &str, value: &Value);
+    /// The leaves of the config with their key, asked for by `config list`
+    fn config_values(&self, values: &[(String, &Value)]);
+}
+
+/// The coloured messages, the progress of the parsing is shown on the lines of a `SubTerminal`
+struct HumanReporter {
+    progress: Mutex<Option<Progress>>,
+    /// The thread redrawing the progress, so that the times move while the assets are parsed
+    ticker: Mutex<Option<JoinHandle<()>>>,
+}
+
+/// The progress of the parsing: the slowest assets being parsed, then a bar with the counts and the times
+/// When stdout isn't a terminal each asset gets a line instead
+struct Progress {
+    terminal: SubTerminal,
+    /// Whether stdout is a terminal, the lines are rewritten in place
+    interactive: bool,
+    /// Number of lines showing the assets being parsed, the bar is on the next one
+    asset_lines: usize,
+    done: usize,
+    total: usize,
+    start: Instant,
+    /// When the last asset was parsed, the times stop there
+    end: Option<Instant>,
+    /// The assets being parsed, in the order they were started
+    in_progress: Vec<(PathBuf, Instant)>,
+    /// The timings of the parsed assets, printed after the progress when it is rewritten in place
+    timings: Vec<String>,
+}
+
+impl Progress {
+    fn draw(&mut self) {
+        // The oldest assets are the slowest ones
+        for line in 0..self.asset_lines {
+            let text = match self.in_progress.get(line) {
+                Some((file, start)) => cformat!(
+                    "Parsing",
+                    format!("{} ({:.1}s)", file.display(), start.elapsed().as_secs_f64()) => Cyan
+                ),
+                None => String::new(),
+            };
+            self.terminal.rewrite_to(&text, line);
+        }
+        let bar = self.bar();
+        self.terminal.rewrite_to(&bar, self.asset_lines);
+    }
+
+    /// `Progress [=========>          ] 12/25 assets, 4.1/s, 0:02 elapsed, ETA 0:03`
+    fn bar(&self) -> String {
+        let elapsed = self.end.unwrap_or_else(Instant::now) - self.start;
+        let filled = BAR_WIDTH * self.done / self.total.max(1);
+        let bar = format!(
+            "[{}{}]",
+            "=".repeat(filled.saturating_sub(1)) + if filled > 0 { ">" } else { "" },
+            " ".repeat(BAR_WIDTH - filled)
+        );
+
+        let rate = self.done as f64 / elapsed.as_secs_f64().max(f64::EPSILON);
+        let eta = if self.done == 0 {
+            "--".to_string()
+        } else {
+            let remaining = (self.total - self.done) as f64 / rate;
+            duration(Duration::from_secs_f64(remaining))
+        };
+
+        cformat!(
+            "Progress",
+            format!(
+                "{bar} {}/{} assets, {rate:.1}/s, {} elapsed, ETA {eta}",
+                self.done,
+                self.total,
+                duration(elapsed)
+            ) => Cyan
+        )
+    }
+}
+
+/// `Timings `player.png` decode 1.2ms, edges 0.4ms, rdp 0.1ms, triangulation 0.2ms, 312 -> 24 vertices`
+fn timings(file: &Path, stats: &Stats) -> String {
+    let milliseconds = |duration: Duration| format!("{:.1}ms", duration.as_secs_f64() * 1000.);
+    let message = format!(
+        "`{}` decode {}, edges {}, rdp {}, triangulation {}, {} -> {} vertices",
+        file.display(),
+        milliseconds(stats.decode),
+        milliseconds(stats.edges),
+        milliseconds(stats.rdp),
+        milliseconds(stats.triangulation),
+        stats.vertices_before,
+        stats.vertices_after
+    );
+    cformat!("Timings", message => Blue)
+}
+
+/// A duration as `m:ss`
+fn duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+impl Reporter for HumanReporter {
+    fn status(&self, label: &str, message: &str) {
+        if verbosity() > Verbosity::Quiet {
+            cprintln!(label, message);
+        }
+    }
+
+    fn warning(&self, message: &str) {
+        if verbosity() == Verbosity::Quiet {
+            return;
+        }
+        let message = format!("Warning {message}");
+        cprintln!(message => Yellow);
+    }
+
+    fn error(&self, message: &str) {
+        let message = format!("Error {message}");
+        ceprintln!(message);
+    }
+
+    fn diagnostic(&self, diagnostic: &Diagnostic) {
+        if diagnostic.level() == Level::Error || verbosity() > Verbosity::Quiet {
+            diagnostic.eprint();
+        }
+    }
+
+    fn parsing_started(&self, total: usize, workers: usize) {
+        if verbosity() == Verbosity::Quiet {
+            return;
+        }
+
+        let interactive = stdout().is_tty();
+        // The bar and the line after the progress must fit in the terminal
+        let rows = terminal::size().map_or(usize::MAX, |(_, rows)| rows as usize);
+        let asset_lines = if interactive {
+            workers.min(MAX_ASSET_LINES).min(rows.saturating_sub(2))
+        } else {
+            0
+        };
+
+        let mut progress = Progress {
+            terminal: SubTerminal::new(asset_lines as u16 + 1),
+            interactive,
+            asset_lines,
+            done: 0,
+            total,
+            start: Instant::now(),
+            end: None,
+            in_progress: vec![],
+            timings: vec![],
+        };
+        if interactive {
+            progress.draw();
+        }
+        *self.progress.lock().unwrap() = Some(progress);
+
+        if interactive {
+            *self.ticker.lock().unwrap() = Some(thread::spawn(|| loop {
+                thread::park_timeout(TICK);
+                match HUMAN.progress.lock().unwrap().as_mut() {
+                    Some(progress) => progress.draw(),
+                    None => break,
+                }
+            }));
+        }
+    }
+
+    fn asset_started(&self, file: &Path) {
+        let mut progress = self.progress.lock().unwrap();
+        let Some(progress) = progress.as_mut() else {
+            return;
+        };
+        progress
+            .in_progress
+            .push((file.to_path_buf(), Instant::now()));
+
+        if progress.interactive {
+            progress.draw();
+        } else {
+            progress.terminal.write_to(
+                cformat!("Parsing", file.to_str().unwrap() => Cyan).as_str(),
+                0,
+            );
+        }
+    }
+
+    fn asset_finished(&self, file: &Path, _checksum: &str, result: AssetResult) {
+        // The counts, the rate and the ETA of the progress are those of the assets being parsed
+        if let AssetResult::Cached(_) = result {
+            return;
+        }
+        let mut progress = self.progress.lock().unwrap();
+        let Some(progress) = progress.as_mut() else {
+            return;
+        };
+        progress.in_progress.retain(|(parsed, _)| parsed != file);
+        progress.done += 1;
+        if progress.done == progress.total {
+            progress.end = Some(Instant::now());
+        }
+
+        let timings = match result {
+            AssetResult::Parsed(_, stats) if verbosity() >= Verbosity::Verbose => {
+                Some(timings(file, stats))
+            }
+            _ => None,
+        };
+
+        if progress.interactive {
+            progress.timings.extend(timings);
+            progress.draw();
+            return;
+        }
+
+        let file_name = file.file_name().unwrap().to_str().unwrap();
+        let count = format!("[{}/{}]", progress.done, progress.total);
+        let text = match result {
+            AssetResult::Parsed(hitbox, _) | AssetResult::Cached(hitbox) => cformat!(
+                "Parsed",
+                format!("`{}` to {} {count}", file_name, hitbox.summary())
+            ),
+            AssetResult::Failed(_) => {
+                cformat!("Failed", format!("`{}` {count}", file_name) => Red)
+            }
+        };
+        progress.terminal.write_to(&text, 0);
+        if let Some(timings) = timings {
+            progress.terminal.write_to(&timings, 0);
+        }
+    }
+
+    fn parsing_finished(&self) {
+        let progress = self.progress.lock().unwrap().take();
+        if let Some(ticker) = self.ticker.lock().unwrap().take() {
+            // The ticker stops once it sees that the progress was taken
+            ticker.thread().unpark();
+            ticker.join().ok();
+        }
+        if let Some(mut progress) = progress {
+            if progress.interactive {
+                // The bar is kept with its final counts, the lines of the assets are emptied
+                progress.draw();
+            }
+            progress.terminal.move_to_last_line_and_new_line();
+            for timings in progress.timings {
+                println!("{timings}");
+            }
+        }
+    }
+
+    fn summary(&self, summary: &Summary) {
+        if verbosity() == Verbosity::Quiet {
+            return;
+        }
+        let mut counts = vec![
+            format!("{} parsed", summary.parsed),
+            format!("{} cached", summary.cached),
+        ];
+        if summary.failed > 0 {
+            counts.push(format!("{} failed", summary.failed));
+        }
+        if summary.skipped() > 0 {
+            counts.push(format!("{} skipped", summary.skipped()));
+        }
+        let message = format!(
+            "{} assets in {:.2}s: {}",
+            summary.assets,
+            summary.duration.as_secs_f64(),
+            counts.join(", ")
+        );
+        cprintln!("Finished", message);
+    }
+
+    fn pattern_expanded(&self, pattern: &str, files: &[PathBuf]) {
+        if verbosity() < Verbosity::Debug {
+            return;
+        }
+        let files: Vec<String> = files
+            .iter()
+            .map(|file| format!("`{}`", file.display()))
+            .collect();
+        let message = match files.len() {
+            0 => format!("`{pattern}` to no file"),
+            1 => format!("`{pattern}` to {}", files[0]),
+            count => format!("`{pattern}` to {count} files: {}", files.join(", ")),
+        };
+        cprintln!("Matched", message => Blue);
+    }
+
+    fn resolved_config(&self, config: &Config) {
+        if verbosity() < Verbosity::Debug {
+            return;
+        }
+        // The lines after the first are aligned with the messages, after the labels
+        let config = serde_json::to_string_pretty(config)
+            .unwrap()
+            .replace('\n', &format!("\n{:13}", ""));
+        cprintln!("Config", config => Blue);
+    }
+
+    fn diff(&self, file: &str, old: &str, new: &str) {
+        if verbosity() == Verbosity::Quiet {
+            return;
+        }
+
+        println!("{}", format!("--- {file}").bright_red());
+        println!("{}", format!("+++ {file} (new)").bright_green());
+        let diff = TextDiff::from_lines(old, new);
+        for hunk in diff
+            .unified_diff()
+            .context_radius(DIFF_CONTEXT)
+            .iter_hunks()
+        {
+            println!("{}", hunk.header().to_string().bright_cyan());
+            for change in hunk.iter_changes() {
+                let line = format!("{}{}", change.tag(), change.value().trim_end_matches('\n'));
+                match change.tag() {
+                    ChangeTag::Delete => println!("{}", line.bright_red()),
+                    ChangeTag::Insert => println!("{}", line.bright_green()),
+                    ChangeTag::Equal => println!("{line}"),
+                }
+            }
+        }
+    }
+
+    // The values are the output of the command, they are printed whatever the verbosity
+    fn config_value(&self, _key: &str, value: &Value) {
+        match value {
+            Value::String(string) => println!("{string}"),
+            value => println!("{}", serde_json::to_string_pretty(value).unwrap()),
+        }
+    }
+
+    fn config_values(&self, values: &[(String, &Value)]) {
+        for (key, value) in values {
+            println!("{key} = {value}");
+        }
+    }
+}
+
+/// One line of JSON per event
+struct JsonReporter {
+    destination: Destination,
+}
+
+#[derive(PartialEq)]
+enum Destination {
+    /// The events kept by the verbosity, with `--message-format json`
+    Stdout,
+    /// All the events, whatever the verbosity
+    Log,
+}
+
+impl JsonReporter {
+    fn emit(&self, event: Value) {
+        if self.destination == Destination::Log {
+            run_log::write(event);
+            return;
+        }
+
+        if verbosity() == Verbosity::Quiet
+            && !QUIET_EVENTS
+                .iter()
+                .any(|quiet_event| event["event"] == *quiet_event)
+        {
+            return;
+        }
+        let mut stdout = stdout().lock();
+        // A closed stdout only means that nobody reads the events anymore
+        writeln!(stdout, "{event}").ok();
+        stdout.flush().ok();
+    }
+
+    /// Whether the events of the verbosity are emitted
+    fn shows(&self, level: Verbosity) -> bool {
+        self.destination == Destination::Log || verbosity() >= level
+    }
+}
+
+impl Reporter for JsonReporter {
+    fn status(&self, label: &str, message: &str) {
+        self.emit(json!({
+            "event": "status",
+            "message": format!("{label} {message}"),
+        }));
+    }
+
+    fn warning(&self, message: &str) {
+        self.emit(json!({ "event": "warning", "message": message }));
+    }
+
+    fn error(&self, message: &str) {
+        self.emit(json!({ "event": "error", "message": message }));
+    }
+
+    fn diagnostic(&self, diagnostic: &Diagnostic) {
+        let mut event = serde_json::to_value(diagnostic).unwrap();
+        let level = match diagnostic.level() {
+            Level::Error => "error",
+            Level::Warning => "warning",
+        };
+        if let Value::Object(fields) = &mut event {
+            fields.shift_insert(0, "event".to_string(), level.into());
+        }
+        self.emit(event);
+    }
+
+    fn parsing_started(&self, _total: usize, _workers: usize) {}
+
+    fn asset_started(&self, file: &Path) {
+        self.emit(json!({ "event": "asset-started", "file": file }));
+    }
+
+    fn asset_finished(&self, file: &Path, checksum: &str, result: AssetResult) {
+        let event = match result {
+            AssetResult::Parsed(hitbox, _) | AssetResult::Cached(hitbox) => {
+                let (kind, count) = match hitbox {
+                    Hitbox::Shapes(shapes) => ("shapes", shapes.len()),
+                    Hitbox::Frames { frames } => ("frames", frames.len()),
+                };
+                let mut event = json!({
+                    "event": "asset-finished",
+                    "file": file,
+                    "checksum": checksum,
+                    "status": "cached",
+                    kind: count,
+                    "triangles": hitbox.triangles_count(),
+                });
+                if let AssetResult::Parsed(_, stats) = result {
+                    event["status"] = "parsed".into();
+                    event["timings"] = json!({
+                        "decode": stats.decode.as_secs_f64(),
+                        "edges": stats.edges.as_secs_f64(),
+                        "rdp": stats.rdp.as_secs_f64(),
+                        "triangulation": stats.triangulation.as_secs_f64(),
+                    });
+                    event["vertices"] = json!({
+                        "before": stats.vertices_before,
+                        "after": stats.vertices_after,
+                    });
+                }
+                event
+            }
+            AssetResult::Failed(error) => json!({
+                "event": "asset-finished",
+                "file": file,
+                "checksum": checksum,
+                "status": "failed",
+                "error": error.to_string(),
+            }),
+        };
+        self.emit(event);
+    }
+
+    fn parsing_finished(&self) {}
+
+    fn summary(&self, summary: &Summary) {
+        self.emit(json!({
+            "event": "summary",
+            "assets": summary.assets,
+            "parsed": summary.parsed,
+            "cached": summary.cached,
+            "failed": summary.failed,
+            "skipped": summary.skipped(),
+            "duration": summary.duration.as_secs_f64(),
+        }));
+    }
+
+    fn pattern_expanded(&self, pattern: &str, files: &[PathBuf]) {
+        if self.shows(Verbosity::Debug) {
+            self.emit(json!({
+                "event": "pattern-expanded",
+                "pattern": pattern,
+                "files": files,
+            }));
+        }
+    }
+
+    fn resolved_config(&self, config: &Config) {
+        if self.shows(Verbosity::Debug) {
+            self.emit(json!({ "event": "resolved-config", "config": config }));
+        }
+    }
+
+    fn diff(&self, file: &str, old: &str, new: &str) {
+        let diff = TextDiff::from_lines(old, new)
+            .unified_diff()
+            .context_radius(DIFF_CONTEXT)
+            .header(file, &format!("{file} (new)"))
+            .to_string();
+        self.emit(json!({ "event": "diff", "file": file, "diff": diff }));
+    }
+
+    fn config_value(&self, key: &str, value: &Value) {
+        self.emit(json!({ "event": "config-value", "key": key, "value": value }));
+    }
+
+    fn config_values(&self, values: &[(String, &Value)]) {
+        for (key, value) in values {
+            self.config_value(key, value);
+        }
+    }
+}
+
+/// Report to the output and to the log of the run
+struct LoggedReporter;
+
+impl Reporter for LoggedReporter {
+    fn status(&self, label: &str, message: &str) {
+        output().status(label, message);
+        LOG.status(label, message);
+    }
+
+    fn warning(&self, message: &str) {
+        output().warning(message);
+        LOG.warning(message);
+    }
+
+    fn error(&self, message: &str) {
+        output().error(message);
+        LOG.error(message);
+    }
+
+    fn diagnostic(&self, diagnostic: &Diagnostic) {
+        output().diagnostic(diagnostic);
+        LOG.diagnostic(diagnostic);
+    }
+
+    fn parsing_started(&self, total: usize, workers: usize) {
+        output().parsing_started(total, workers);
+        LOG.parsing_started(total, workers);
+    }
+
+    fn asset_started(&self, file: &Path) {
+        output().asset_started(file);
+        LOG.asset_started(file);
+    }
+
+    fn asset_finished(&self, file: &Path, checksum: &str, result: AssetResult) {
+        output().asset_finished(file, checksum, result);
+        LOG.asset_finished(file, checksum, result);
+    }
+
+    fn parsing_finished(&self) {
+        output().parsing_finished();
+        LOG.parsing_finished();
+    }
+
+    fn summary(&self, summary: &Summary) {
+        output().summary(summary);
+        LOG.summary(summary);
+    }
+
+    fn pattern_expanded(&self, pattern: &str, files: &[PathBuf]) {
+        output().pattern_expanded(pattern, files);
+        LOG.pattern_expanded(pattern, files);
+    }
+
+    fn resolved_config(&self, config: &Config) {
+        output().resolved_config(config);
+        LOG.resolved_config(config);
+    }
+
+    fn diff(&self, file: &str, old: &str, new: &str) {
+        output().diff(file, old, new);
+        LOG.diff(file, old, new);
+    }
+
+    fn config_value(&self, key: &str, value: &Value) {
+        output().config_value(key, value);
+        LOG.config_value(key, value);
+    }
+
+    fn config_values(&self, values: &[(String, &Value)]) {
+        output().config_values(values);
+        LOG.config_values(values);
+    }
+}
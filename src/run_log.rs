@@ -0,0 +1,168 @@
+//! The log of the runs writing to the project (see `SubCommandEnum::is_logged`), written to `.cazan/logs/` so that a
+//! prebuild can be inspected after the terminal is gone
+//! It has the same events as `--message-format json`, whatever the verbosity, between a `run` event with the command
+//! line and an `exit` event. Each event gets the seconds `elapsed` since the start of the run
+//!
+//! ```text
+//! {"event":"run","time":"2026-10-18T14:03:22.120+02:00","version":"0.1.0","args":["cazan","prebuild"],"directory":"/home/me/game","elapsed":0.0}
+//! {"event":"resolved-config","config":{"name":"game","version":"1.0.0","authors":[],"assets":["*.png"],"plugins":null},"elapsed":0.001}
+//! {"event":"exit","success":true,"elapsed":0.02}
+//! ```
+
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, LineWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Instant;
+
+use chrono::Local;
+use serde_json::{json, Value};
+
+/// Number of logs kept when the config has no `maxLogs`
+const DEFAULT_MAX_LOGS: usize = 20;
+
+static LOG: Mutex<Option<RunLog>> = Mutex::new(None);
+
+struct RunLog {
+    /// Each event is written at once, the log is complete up to a crash
+    writer: LineWriter<File>,
+    start: Instant,
+}
+
+/// Start the log of the run in the `.cazan` directory of the project, the oldest logs are removed to keep `maxLogs`
+/// of them, with the new one. No log is started when `maxLogs` is 0
+pub fn start(cazan_directory: &Path) -> Result<(), io::Error> {
+    let logs_directory = cazan_directory.join("logs");
+    let max_logs = max_logs(cazan_directory);
+
+    if logs_directory.exists() {
+        rotate(&logs_directory, max_logs.saturating_sub(1))?;
+    }
+    if max_logs == 0 {
+        return Ok(());
+    }
+
+    fs::create_dir_all(&logs_directory)?;
+    let now = Local::now();
+    // The names sort in the order of the runs, the process id separates the runs started in the same millisecond
+    let path = logs_directory.join(format!(
+        "{}-{}.jsonl",
+        now.format("%Y-%m-%dT%H-%M-%S%.3f"),
+        std::process::id()
+    ));
+
+    *LOG.lock().unwrap() = Some(RunLog {
+        writer: LineWriter::new(File::create(&path)?),
+        start: Instant::now(),
+    });
+
+    write(json!({
+        "event": "run",
+        "time": now.to_rfc3339(),
+        "version": env!("CARGO_PKG_VERSION"),
+        "args": env::args().collect::<Vec<_>>(),
+        "directory": env::current_dir().ok(),
+    }));
+
+    Ok(())
+}
+
+/// Add an event to the log, nothing is done when no log was started
+pub fn write(mut event: Value) {
+    let mut log = LOG.lock().unwrap();
+    let Some(log) = log.as_mut() else {
+        return;
+    };
+
+    if let Value::Object(fields) = &mut event {
+        fields.insert(
+            "elapsed".to_string(),
+            log.start.elapsed().as_secs_f64().into(),
+        );
+    }
+    // The run isn't stopped for its log
+    writeln!(log.writer, "{event}").ok();
+}
+
+/// End the log with the outcome of the run
+pub fn finish(success: bool) {
+    write(json!({ "event": "exit", "success": success }));
+    LOG.lock().unwrap().take();
+}
+
+/// `maxLogs` of the locked config, it is read without the rest of the config which may be invalid
+fn max_logs(cazan_directory: &Path) -> usize {
+    fs::read_to_string(cazan_directory.join("config.json"))
+        .ok()
+        .and_then(|config| serde_json::from_str::<Value>(&config).ok())
+        .and_then(|config| config["maxLogs"].as_u64())
+        .map_or(DEFAULT_MAX_LOGS, |max_logs| max_logs as usize)
+}
+
+/// Remove the oldest logs so that `keep` of them are left
+fn rotate(logs_directory: &Path, keep: usize) -> Result<(), io::Error> {
+    let mut logs: Vec<PathBuf> = fs::read_dir(logs_directory)?
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "jsonl")
+        })
+        .collect();
+    logs.sort();
+
+    let removed = logs.len().saturating_sub(keep);
+    for log in &logs[..removed] {
+        fs::remove_file(log)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn logs(directory: &Path) -> Vec<String> {
+        let mut logs: Vec<String> = fs::read_dir(directory)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        logs.sort();
+        logs
+    }
+
+    #[test]
+    fn rotate_removes_the_oldest_logs() {
+        let directory = tempfile::tempdir().unwrap();
+        for name in [
+            "2026-01-03.jsonl",
+            "2026-01-01.jsonl",
+            "2026-01-02.jsonl",
+            "notes.txt",
+        ] {
+            fs::write(directory.path().join(name), "").unwrap();
+        }
+
+        rotate(directory.path(), 2).unwrap();
+        assert_eq!(
+            logs(directory.path()),
+            ["2026-01-02.jsonl", "2026-01-03.jsonl", "notes.txt"]
+        );
+
+        rotate(directory.path(), 0).unwrap();
+        assert_eq!(logs(directory.path()), ["notes.txt"]);
+    }
+
+    #[test]
+    fn max_logs_of_the_locked_config() {
+        let directory = tempfile::tempdir().unwrap();
+        assert_eq!(max_logs(directory.path()), DEFAULT_MAX_LOGS);
+
+        fs::write(directory.path().join("config.json"), r#"{ "maxLogs": 3 }"#).unwrap();
+        assert_eq!(max_logs(directory.path()), 3);
+
+        // An invalid locked config doesn't stop the logs
+        fs::write(directory.path().join("config.json"), "{").unwrap();
+        assert_eq!(max_logs(directory.path()), DEFAULT_MAX_LOGS);
+    }
+}
@@ -0,0 +1,301 @@
+//! Slicing of the sprite sheets into frames
+//! The frames are described by a grid, or by a sidecar JSON file exported by Aseprite or TexturePacker
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// How a sprite sheet is sliced
+#[derive(Clone, PartialEq, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Slicing {
+    Grid {
+        width: u32,
+        height: u32,
+        margin: u32,
+        spacing: u32,
+        count: Option<u32>,
+    },
+    /// Path of the sidecar relative to the directory of the sprite sheet, `{stem}` is replaced by the file stem of the
+    /// sprite sheet
+    Sidecar(String),
+}
+
+/// A frame of a sprite sheet
+#[derive(Debug)]
+pub struct Frame {
+    /// Region of the frame in the sprite sheet
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    /// The frame is stored rotated 90° clockwise in the sprite sheet (TexturePacker)
+    pub rotated: bool,
+    /// Position of the region in the original frame, when the transparent borders were trimmed
+    pub offset: (u32, u32),
+}
+
+#[derive(Deserialize)]
+struct Rect {
+    x: u32,
+    y: u32,
+    w: u32,
+    h: u32,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SidecarFrame {
+    frame: Rect,
+    #[serde(default)]
+    rotated: bool,
+    #[serde(default)]
+    trimmed: bool,
+    sprite_source_size: Option<Rect>,
+}
+
+impl Slicing {
+    /// The frames of the sprite sheet, in their order of animation
+    pub fn frames(&self, file: &Path, size: (u32, u32)) -> Result<Vec<Frame>, String> {
+        let frames = match self {
+            Slicing::Grid {
+                width,
+                height,
+                margin,
+                spacing,
+                count,
+            } => grid_frames(size, *width, *height, *margin, *spacing, *count),
+            Slicing::Sidecar(path) => sidecar_frames(&sidecar_path(file, path))?,
+        };
+
+        for (i, frame) in frames.iter().enumerate() {
+            let (width, height) = if frame.rotated {
+                (frame.height, frame.width)
+            } else {
+                (frame.width, frame.height)
+            };
+            let fits = |start: u32, length: u32, size: u32| {
+                start.checked_add(length).is_some_and(|end| end <= size)
+            };
+            if !fits(frame.x, width, size.0) || !fits(frame.y, height, size.1) {
+                return Err(format!("frame {i} is outside of the sprite sheet"));
+            }
+        }
+
+        Ok(frames)
+    }
+}
+
+/// Path of the sidecar of a sprite sheet, the exports of Aseprite and TexturePacker are next to their sheet
+pub fn sidecar_path(file: &Path, path: &str) -> PathBuf {
+    let stem = file
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or_default();
+    let directory = file.parent().unwrap_or(Path::new(""));
+    directory.join(path.replace("{stem}", stem))
+}
+
+fn grid_frames(
+    size: (u32, u32),
+    width: u32,
+    height: u32,
+    margin: u32,
+    spacing: u32,
+    count: Option<u32>,
+) -> Vec<Frame> {
+    if width == 0 || height == 0 {
+        return vec![];
+    }
+
+    // Computed on 64 bits so that no margin or spacing overflows, the frames of the grid are inside the sprite sheet
+    let (margin, spacing) = (u64::from(margin), u64::from(spacing));
+    let fit = |size: u32, frame: u32| {
+        (u64::from(size).saturating_sub(2 * margin) + spacing) / (u64::from(frame) + spacing)
+    };
+    let columns = fit(size.0, width);
+    let rows = fit(size.1, height);
+    let count = count.map_or(columns * rows, u64::from).min(columns * rows);
+
+    (0..count)
+        .map(|i| Frame {
+            x: (margin + (i % columns) * (u64::from(width) + spacing)) as u32,
+            y: (margin + (i / columns) * (u64::from(height) + spacing)) as u32,
+            width,
+            height,
+            rotated: false,
+            offset: (0, 0),
+        })
+        .collect()
+}
+
+/// Read the frames of a sidecar, both the hash and the array layouts are supported
+fn sidecar_frames(path: &Path) -> Result<Vec<Frame>, String> {
+    let sidecar = fs::read_to_string(path)
+        .map_err(|e| format!("couldn't read the sidecar `{}`: {e}", path.display()))?;
+    let sidecar: Value = serde_json::from_str(&sidecar)
+        .map_err(|e| format!("the sidecar `{}` is invalid: {e}", path.display()))?;
+
+    let frames: Vec<Value> = match sidecar.get("frames") {
+        Some(Value::Array(frames)) => frames.clone(),
+        Some(Value::Object(frames)) => frames.values().cloned().collect(),
+        _ => return Err(format!("the sidecar `{}` has no `frames`", path.display())),
+    };
+
+    frames
+        .into_iter()
+        .map(|frame| {
+            let frame: SidecarFrame = serde_json::from_value(frame)
+                .map_err(|e| format!("the sidecar `{}` is invalid: {e}", path.display()))?;
+            let offset = match (frame.trimmed, frame.sprite_source_size) {
+                (true, Some(source)) => (source.x, source.y),
+                _ => (0, 0),
+            };
+            Ok(Frame {
+                x: frame.frame.x,
+                y: frame.frame.y,
+                width: frame.frame.w,
+                height: frame.frame.h,
+                rotated: frame.rotated,
+                offset,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn positions(frames: &[Frame]) -> Vec<(u32, u32)> {
+        frames.iter().map(|frame| (frame.x, frame.y)).collect()
+    }
+
+    fn grid(width: u32, height: u32, margin: u32, spacing: u32, count: Option<u32>) -> Slicing {
+        Slicing::Grid {
+            width,
+            height,
+            margin,
+            spacing,
+            count,
+        }
+    }
+
+    #[test]
+    fn grid_with_margin_and_spacing() {
+        let frames = grid(10, 8, 2, 1, None)
+            .frames(Path::new("sheet.png"), (36, 30))
+            .unwrap();
+        assert_eq!(
+            positions(&frames),
+            [
+                (2, 2),
+                (13, 2),
+                (24, 2),
+                (2, 11),
+                (13, 11),
+                (24, 11),
+                (2, 20),
+                (13, 20),
+                (24, 20)
+            ]
+        );
+        assert!(frames
+            .iter()
+            .all(|frame| (frame.width, frame.height) == (10, 8)));
+
+        // The frames left over at the right and the bottom are ignored
+        let frames = grid(16, 16, 0, 0, None)
+            .frames(Path::new("sheet.png"), (40, 20))
+            .unwrap();
+        assert_eq!(positions(&frames), [(0, 0), (16, 0)]);
+    }
+
+    #[test]
+    fn grid_count_stops_at_the_last_frame() {
+        let sheet = Path::new("sheet.png");
+        let frames = grid(8, 8, 0, 0, Some(3)).frames(sheet, (16, 16)).unwrap();
+        assert_eq!(positions(&frames), [(0, 0), (8, 0), (0, 8)]);
+
+        let frames = grid(8, 8, 0, 0, Some(10)).frames(sheet, (16, 16)).unwrap();
+        assert_eq!(frames.len(), 4);
+    }
+
+    #[test]
+    fn grid_without_room_for_a_frame() {
+        let sheet = Path::new("sheet.png");
+        assert!(grid(0, 8, 0, 0, None)
+            .frames(sheet, (16, 16))
+            .unwrap()
+            .is_empty());
+        assert!(grid(32, 8, 0, 0, None)
+            .frames(sheet, (16, 16))
+            .unwrap()
+            .is_empty());
+        assert!(grid(8, 8, u32::MAX, 0, None)
+            .frames(sheet, (16, 16))
+            .unwrap()
+            .is_empty());
+
+        let frames = grid(8, 8, 0, u32::MAX, None)
+            .frames(sheet, (16, 16))
+            .unwrap();
+        assert_eq!(positions(&frames), [(0, 0)]);
+    }
+
+    #[test]
+    fn sidecar_frames_are_checked_against_the_sheet() {
+        let directory = tempfile::tempdir().unwrap();
+        let sidecar = Slicing::Sidecar("{stem}.json".to_string());
+        let chars = directory.path().join("assets/chars");
+        fs::create_dir_all(&chars).unwrap();
+        let sheet = chars.join("hero.png");
+
+        fs::write(
+            chars.join("hero.json"),
+            r#"{ "frames": {
+                "idle": { "frame": { "x": 0, "y": 0, "w": 8, "h": 4 }, "rotated": true,
+                          "trimmed": true, "spriteSourceSize": { "x": 1, "y": 2, "w": 8, "h": 4 } },
+                "run": { "frame": { "x": 4, "y": 0, "w": 12, "h": 16 } }
+            } }"#,
+        )
+        .unwrap();
+        let frames = sidecar.frames(&sheet, (16, 16)).unwrap();
+        assert_eq!(positions(&frames), [(0, 0), (4, 0)]);
+        assert!(frames[0].rotated);
+        assert_eq!(frames[0].offset, (1, 2));
+        assert_eq!(frames[1].offset, (0, 0));
+
+        // Rotated, the first frame is 4 pixels wide and 8 pixels high
+        assert!(sidecar.frames(&sheet, (16, 7)).is_err());
+
+        fs::write(
+            chars.join("hero.json"),
+            r#"{ "frames": [{ "frame": { "x": 4294967295, "y": 0, "w": 8, "h": 8 } }] }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            sidecar.frames(&sheet, (16, 16)).unwrap_err(),
+            "frame 0 is outside of the sprite sheet"
+        );
+    }
+
+    #[test]
+    fn sidecars_are_next_to_their_sheet() {
+        let sheet = Path::new("assets/chars/hero.png");
+        assert_eq!(
+            sidecar_path(sheet, "{stem}.json"),
+            Path::new("assets/chars/hero.json")
+        );
+        assert_eq!(
+            sidecar_path(sheet, "../frames/{stem}.json"),
+            Path::new("assets/chars/../frames/hero.json")
+        );
+        assert_eq!(
+            sidecar_path(Path::new("hero.png"), "{stem}.json"),
+            Path::new("hero.json")
+        );
+    }
+}
@@ -0,0 +1,211 @@
+//! Project templates laid down by `cazan init --template`
+//! A template is a set of files, the built-in ones are embedded in the binary and the local ones are read from a
+//! directory. The `{{name}}` and `{{version}}` variables of the text files are replaced by the values of the project,
+//! in the JSON, TOML and YAML files they are expected in double-quoted strings and the values are escaped for them
+
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A built-in template, its files are relative to the project root
+struct BuiltinTemplate {
+    name: &'static str,
+    files: &'static [(&'static str, &'static [u8])],
+}
+
+macro_rules! template_file {
+    ($template:literal, $file:literal) => {
+        (
+            $file,
+            include_bytes!(concat!("../templates/", $template, "/", $file)),
+        )
+    };
+}
+
+const BUILTIN_TEMPLATES: &[BuiltinTemplate] = &[
+    BuiltinTemplate {
+        name: "default",
+        files: &[
+            template_file!("default", "cazan.json"),
+            template_file!("default", ".gitignore"),
+            template_file!("default", "assets/sprites/player.png"),
+            template_file!("default", "assets/audio/.gitkeep"),
+        ],
+    },
+    BuiltinTemplate {
+        name: "spritesheet",
+        files: &[
+            template_file!("spritesheet", "cazan.json"),
+            template_file!("spritesheet", ".gitignore"),
+            template_file!("spritesheet", "assets/sprites/ball.png"),
+            template_file!("spritesheet", "assets/audio/.gitkeep"),
+        ],
+    },
+    BuiltinTemplate {
+        name: "minimal",
+        files: &[
+            template_file!("minimal", "cazan.json"),
+            template_file!("minimal", ".gitignore"),
+            template_file!("minimal", "assets/.gitkeep"),
+        ],
+    },
+];
+
+/// The values of the variables of a template
+pub struct Variables<'a> {
+    pub name: &'a str,
+    pub version: &'a str,
+}
+
+pub struct Template {
+    /// The files with their path relative to the project root
+    files: Vec<(PathBuf, Vec<u8>)>,
+}
+
+impl Template {
+    /// Load a built-in template by its name, or a local template from a directory
+    pub fn load(name_or_path: &str) -> Result<Self, String> {
+        if let Some(template) = BUILTIN_TEMPLATES
+            .iter()
+            .find(|template| template.name == name_or_path)
+        {
+            return Ok(Self {
+                files: template
+                    .files
+                    .iter()
+                    .map(|(path, content)| (PathBuf::from(path), content.to_vec()))
+                    .collect(),
+            });
+        }
+
+        let directory = Path::new(name_or_path);
+        if !directory.is_dir() {
+            return Err(format!(
+                "`{name_or_path}` is neither a built-in template ({}) nor a directory",
+                BUILTIN_TEMPLATES
+                    .iter()
+                    .map(|template| template.name)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+
+        let mut files = vec![];
+        read_directory(directory, directory, &mut files)
+            .map_err(|e| format!("couldn't read the template `{name_or_path}`: {e}"))?;
+        files.sort();
+
+        Ok(Self { files })
+    }
+
+    /// The files of the template with the variables replaced, the binary files are kept as is
+    pub fn render(&self, variables: &Variables) -> Vec<(PathBuf, Vec<u8>)> {
+        self.files
+            .iter()
+            .map(|(path, content)| {
+                let content = match std::str::from_utf8(content) {
+                    Ok(text) => text
+                        .replace("{{name}}", &escape(variables.name, path))
+                        .replace("{{version}}", &escape(variables.version, path))
+                        .into_bytes(),
+                    Err(_) => content.clone(),
+                };
+                (path.clone(), content)
+            })
+            .collect()
+    }
+}
+
+/// The value escaped for a double-quoted string in the format of the file, the other files get it as is
+fn escape(value: &str, path: &Path) -> String {
+    let quoted = serde_json::to_string(value).unwrap();
+    let escaped = &quoted[1..quoted.len() - 1];
+
+    match path.extension().and_then(OsStr::to_str) {
+        Some("json") => escaped.to_string(),
+        // A JSON string is a TOML basic string and a YAML double-quoted string, once the control characters JSON
+        // allows (DEL and C1) are escaped too
+        Some("toml" | "yaml" | "yml") => escaped
+            .chars()
+            .map(|c| {
+                if c.is_control() {
+                    format!("\\u{:04x}", c as u32)
+                } else {
+                    c.to_string()
+                }
+            })
+            .collect(),
+        _ => value.to_string(),
+    }
+}
+
+fn read_directory(
+    root: &Path,
+    directory: &Path,
+    files: &mut Vec<(PathBuf, Vec<u8>)>,
+) -> std::io::Result<()> {
+    for entry in fs::read_dir(directory)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            read_directory(root, &path, files)?;
+        } else {
+            let relative = path.strip_prefix(root).unwrap().to_path_buf();
+            files.push((relative, fs::read(&path)?));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NAME: &str = "my \"game\" in C:\\games\n\u{7f}";
+
+    fn render(path: &str, text: &str) -> String {
+        let template = Template {
+            files: vec![(PathBuf::from(path), text.as_bytes().to_vec())],
+        };
+        let variables = Variables {
+            name: NAME,
+            version: "1.0.0",
+        };
+        let files = template.render(&variables);
+        String::from_utf8(files[0].1.clone()).unwrap()
+    }
+
+    #[test]
+    fn values_are_escaped_for_the_format() {
+        let json = render(
+            "cazan.json",
+            r#"{ "name": "{{name}}", "version": "{{version}}" }"#,
+        );
+        let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(json["name"], NAME);
+        assert_eq!(json["version"], "1.0.0");
+
+        let toml = render("cazan.toml", "name = \"{{name}}\"\n");
+        let toml: toml::Table = toml::from_str(&toml).unwrap();
+        assert_eq!(toml["name"].as_str(), Some(NAME));
+
+        for path in ["cazan.yaml", "config/game.yml"] {
+            let yaml = render(path, "name: \"{{name}}\"\n");
+            let yaml: serde_json::Value = serde_norway::from_str(&yaml).unwrap();
+            assert_eq!(yaml["name"], NAME);
+        }
+
+        assert_eq!(render("README.md", "# {{name}}"), format!("# {NAME}"));
+    }
+
+    #[test]
+    fn binary_files_are_kept() {
+        let template = Template {
+            files: vec![(PathBuf::from("player.png"), vec![0x89, 0xff, b'{', b'{'])],
+        };
+        let variables = Variables {
+            name: NAME,
+            version: "1.0.0",
+        };
+        assert_eq!(template.render(&variables)[0].1, [0x89, 0xff, b'{', b'{']);
+    }
+}
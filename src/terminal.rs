@@ -1,65 +1,192 @@
-use crossterm::{cursor, terminal, ExecutableCommand};
+//! The output to the terminal: the colours, and the areas of lines rewritten in place like the progress of the prebuild
+//! When stdout isn't a terminal (CI logs, pipes), the cursor can't be moved, each write is printed on its own line
+
+use crossterm::tty::IsTty;
+use crossterm::{cursor, terminal, QueueableCommand};
+use std::env;
+use std::fmt;
 use std::io::{stdout, StdoutLock, Write};
+use std::str::FromStr;
+
+/// When to colour the output, given with `--color`
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum ColorChoice {
+    /// Only when stdout is a terminal and `NO_COLOR` isn't set
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl FromStr for ColorChoice {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(Self::Auto),
+            "always" => Ok(Self::Always),
+            "never" => Ok(Self::Never),
+            _ => Err(format!(
+                "unknown color choice `{s}`, expected auto, always or never"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for ColorChoice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Auto => write!(f, "auto"),
+            Self::Always => write!(f, "always"),
+            Self::Never => write!(f, "never"),
+        }
+    }
+}
+
+/// Apply the colour choice to the messages, the `cprint` macros and the styled texts use `colored`
+pub fn set_color(choice: ColorChoice) {
+    let enabled = match choice {
+        ColorChoice::Auto => {
+            env::var_os("NO_COLOR").is_none_or(|value| value.is_empty()) && stdout().is_tty()
+        }
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+    };
+    colored::control::set_override(enabled);
+}
 
+/// Restore the default handler of SIGPIPE, Rust ignores it so the prints panic when the reader of a pipe is closed
+/// (ex: `cazan --help | head -1`). With the default handler cazan ends quietly, like the other command line tools
+pub fn reset_sigpipe() {
+    #[cfg(unix)]
+    // SAFETY: called at the start of main, before any other thread exists
+    unsafe {
+        libc::signal(libc::SIGPIPE, libc::SIG_DFL);
+    }
+}
+
+/// Lines of the terminal rewritten in place, `line` of the methods is taken modulo the number of lines
+/// The errors of the writes are ignored, the progress isn't worth stopping the command for
 pub struct SubTerminal {
-    n_lines: u16,
-    current_line: usize,
+    /// The texts of the lines, to redraw them when the window scrolls
+    lines: Vec<String>,
+    /// `None` when stdout isn't a terminal
+    window: Option<Window>,
+}
+
+/// The lines shown on the terminal, when it is shorter than the number of lines they are scrolled
+struct Window {
+    /// Index of the first line shown
+    first: usize,
+    height: usize,
+    /// Row of the cursor, from the top of the window
+    row: usize,
 }
 
 impl SubTerminal {
     pub fn new(n_lines: u16) -> Self {
-        for _ in 0..n_lines - 1 {
-            println!("\r");
+        let n_lines = (n_lines as usize).max(1);
+        let lines = vec![String::new(); n_lines];
+
+        if !stdout().is_tty() {
+            return Self {
+                lines,
+                window: None,
+            };
         }
-        for _ in 0..n_lines {
-            stdout()
-                .lock()
-                .execute(cursor::MoveToPreviousLine(1))
-                .unwrap();
+
+        // The last row of the terminal is kept for the cursor after the lines
+        let rows = terminal::size().map_or(n_lines + 1, |(_, rows)| rows as usize);
+        let height = n_lines.min(rows.saturating_sub(1)).max(1);
+
+        // The rows are reserved by printing line breaks, the terminal scrolls if they are at its bottom
+        let mut stdout = stdout().lock();
+        write!(stdout, "{}", "\n".repeat(height - 1)).ok();
+        if height > 1 {
+            stdout.queue(cursor::MoveUp(height as u16 - 1)).ok();
         }
+        stdout.queue(cursor::MoveToColumn(0)).ok();
+        stdout.flush().ok();
 
         Self {
-            n_lines,
-            current_line: 0,
+            lines,
+            window: Some(Window {
+                first: 0,
+                height,
+                row: 0,
+            }),
         }
     }
 
+    /// Write `text` at the start of the line, over its previous text
     pub fn write_to(&mut self, text: &str, line: usize) {
-        let mut stdout = stdout().lock();
-        self.move_to(&mut stdout, line);
-        write!(stdout, "{}", text).unwrap();
-        stdout.flush().unwrap();
+        self.update(text, line, false);
     }
 
-    fn move_to(&mut self, stdout_lock: &mut StdoutLock, line: usize) {
-        let line = line % self.n_lines as usize;
-        if line < self.current_line {
-            stdout_lock
-                .execute(cursor::MoveUp(self.current_line as u16 - line as u16))
-                .unwrap();
-            stdout_lock.execute(cursor::MoveToColumn(0)).unwrap();
+    /// Replace the text of the line with `text`
+    pub fn rewrite_to(&mut self, text: &str, line: usize) {
+        self.update(text, line, true);
+    }
+
+    fn update(&mut self, text: &str, line: usize, clear: bool) {
+        let line = line % self.lines.len();
+        self.lines[line] = text.to_string();
+
+        let mut stdout = stdout().lock();
+        let Some(window) = &mut self.window else {
+            writeln!(stdout, "{text}").ok();
+            stdout.flush().ok();
+            return;
+        };
+
+        if line < window.first || line >= window.first + window.height {
+            window.first = if line < window.first {
+                line
+            } else {
+                line + 1 - window.height
+            };
+            Self::redraw(&mut stdout, window, &self.lines);
         } else {
-            stdout_lock
-                .execute(cursor::MoveDown(line as u16 - self.current_line as u16))
-                .unwrap();
-            stdout_lock.execute(cursor::MoveToColumn(0)).unwrap();
+            window.move_to(&mut stdout, line - window.first);
+            if clear {
+                stdout
+                    .queue(terminal::Clear(terminal::ClearType::CurrentLine))
+                    .ok();
+            }
+            write!(stdout, "{text}").ok();
         }
-        self.current_line = line;
+        stdout.flush().ok();
     }
 
-    pub fn rewrite_to(&mut self, text: &str, line: usize) {
-        let mut stdout = stdout().lock();
-        self.move_to(&mut stdout, line);
-        stdout.execute(cursor::MoveToColumn(0)).unwrap();
-        stdout
-            .execute(terminal::Clear(terminal::ClearType::CurrentLine))
-            .unwrap();
-        write!(stdout, "{}", text).unwrap();
-        stdout.flush().unwrap();
+    /// Rewrite all the rows of the window, after it scrolled
+    fn redraw(stdout: &mut StdoutLock, window: &mut Window, lines: &[String]) {
+        for row in 0..window.height {
+            window.move_to(stdout, row);
+            stdout
+                .queue(terminal::Clear(terminal::ClearType::CurrentLine))
+                .ok();
+            write!(stdout, "{}", lines[window.first + row]).ok();
+        }
     }
 
     pub fn move_to_last_line_and_new_line(&mut self) {
-        self.move_to(&mut stdout().lock(), self.n_lines as usize - 1);
-        println!();
+        let mut stdout = stdout().lock();
+        if let Some(window) = &mut self.window {
+            window.move_to(&mut stdout, window.height - 1);
+            writeln!(stdout).ok();
+        }
+        stdout.flush().ok();
+    }
+}
+
+impl Window {
+    fn move_to(&mut self, stdout: &mut StdoutLock, row: usize) {
+        if row < self.row {
+            stdout.queue(cursor::MoveUp((self.row - row) as u16)).ok();
+        } else if row > self.row {
+            stdout.queue(cursor::MoveDown((row - self.row) as u16)).ok();
+        }
+        stdout.queue(cursor::MoveToColumn(0)).ok();
+        self.row = row;
     }
 }
@@ -0,0 +1,105 @@
+//! `cazan check` is used in CI, it must leave the project as it is, whatever it finds
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+
+use image::{Rgba, RgbaImage};
+
+fn cazan(directory: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_cazan"))
+        .args(args)
+        .current_dir(directory)
+        .env_remove("NO_COLOR")
+        .output()
+        .unwrap()
+}
+
+/// The files of the directory with their content
+fn snapshot(directory: &Path) -> BTreeMap<String, Vec<u8>> {
+    let mut files = BTreeMap::new();
+    let mut directories = vec![directory.to_path_buf()];
+    while let Some(current) = directories.pop() {
+        for entry in fs::read_dir(current).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                files.insert(
+                    path.strip_prefix(directory).unwrap().display().to_string() + "/",
+                    vec![],
+                );
+                directories.push(path);
+            } else {
+                let name = path.strip_prefix(directory).unwrap().display().to_string();
+                files.insert(name, fs::read(&path).unwrap());
+            }
+        }
+    }
+    files
+}
+
+/// A locked and prebuilt project with one sprite
+fn project() -> tempfile::TempDir {
+    let directory = tempfile::tempdir().unwrap();
+    let sprite = RgbaImage::from_fn(16, 16, |x, y| {
+        let opaque = (4..12).contains(&x) && (4..12).contains(&y);
+        Rgba([255, 0, 0, if opaque { 255 } else { 0 }])
+    });
+    sprite.save(directory.path().join("sprite.png")).unwrap();
+    fs::write(
+        directory.path().join("cazan.json"),
+        r#"{
+  "configVersion": 2,
+  "name": "game",
+  "version": "1.0.0",
+  "authors": [],
+  "assets": ["*.png"],
+  "plugins": null
+}
+"#,
+    )
+    .unwrap();
+    fs::create_dir(directory.path().join(".cazan")).unwrap();
+
+    assert!(cazan(directory.path(), &["lock"]).status.success());
+    assert!(cazan(directory.path(), &["prebuild"]).status.success());
+    directory
+}
+
+fn assert_check_writes_nothing(directory: &Path, expected_code: i32) {
+    for args in [
+        &["check"][..],
+        &["-vv", "check"],
+        &["--message-format", "json", "check"],
+    ] {
+        let before = snapshot(directory);
+        let output = cazan(directory, args);
+        assert_eq!(output.status.code(), Some(expected_code), "{args:?}");
+        assert!(
+            before == snapshot(directory),
+            "`cazan {args:?}` changed the project"
+        );
+    }
+}
+
+#[test]
+fn check_of_an_up_to_date_project() {
+    let project = project();
+    assert_check_writes_nothing(project.path(), 0);
+}
+
+#[test]
+fn check_of_a_stale_project() {
+    let project = project();
+    let config = project.path().join("cazan.json");
+    let text = fs::read_to_string(&config).unwrap();
+    fs::write(&config, text.replace("\"game\"", "\"renamed\"")).unwrap();
+    assert_check_writes_nothing(project.path(), 3);
+}
+
+#[test]
+fn check_of_a_project_without_hitboxes() {
+    let project = project();
+    fs::remove_dir_all(project.path().join(".cazan/build")).unwrap();
+    assert_check_writes_nothing(project.path(), 6);
+}
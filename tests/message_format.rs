@@ -0,0 +1,97 @@
+//! With `--message-format json`, everything written to stdout is one JSON event per line
+
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+
+use serde_json::Value;
+
+fn cazan(directory: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_cazan"))
+        .args(args)
+        .current_dir(directory)
+        .output()
+        .unwrap()
+}
+
+/// The events written to stdout, every line must be a JSON object with an `event`
+fn events(output: &Output) -> Vec<Value> {
+    String::from_utf8(output.stdout.clone())
+        .unwrap()
+        .lines()
+        .map(|line| {
+            let event: Value = serde_json::from_str(line)
+                .unwrap_or_else(|e| panic!("`{line}` isn't a JSON event: {e}"));
+            assert!(event["event"].is_string(), "`{line}` has no event");
+            event
+        })
+        .collect()
+}
+
+/// A project with a config written before `configVersion`
+fn project() -> tempfile::TempDir {
+    let directory = tempfile::tempdir().unwrap();
+    fs::write(
+        directory.path().join("cazan.json"),
+        r#"{
+  "name": "game",
+  "version": "1.0.0",
+  "authors": [],
+  "assets": ["*.png"],
+  "plugins": null
+}
+"#,
+    )
+    .unwrap();
+    fs::create_dir(directory.path().join(".cazan")).unwrap();
+    directory
+}
+
+#[test]
+fn migrate_reports_its_diff_as_an_event() {
+    let directory = project();
+
+    let output = cazan(
+        directory.path(),
+        &["--message-format", "json", "migrate", "--dry-run"],
+    );
+    assert!(output.status.success());
+    let events = events(&output);
+    let diff = events
+        .iter()
+        .find(|event| event["event"] == "diff")
+        .expect("no diff event");
+    assert_eq!(diff["file"], "cazan.json");
+    assert!(diff["diff"]
+        .as_str()
+        .unwrap()
+        .contains("+  \"configVersion\": 2,"));
+
+    let output = cazan(directory.path(), &["-q", "migrate", "--dry-run"]);
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn config_values_are_events() {
+    let directory = project();
+
+    let output = cazan(
+        directory.path(),
+        &["--message-format", "json", "config", "get", "name"],
+    );
+    assert_eq!(
+        events(&output),
+        [serde_json::json!({ "event": "config-value", "key": "name", "value": "game" })]
+    );
+
+    let output = cazan(
+        directory.path(),
+        &["--message-format", "json", "-q", "config", "list"],
+    );
+    let keys: Vec<Value> = events(&output)
+        .into_iter()
+        .map(|event| event["key"].clone())
+        .collect();
+    assert_eq!(keys, ["name", "version", "authors", "assets.0", "plugins"]);
+}